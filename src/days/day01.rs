@@ -1,15 +1,165 @@
+use crate::etc::bench::{self, Rng};
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use std::fs::read_to_string;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::thread;
+
+// inputs at least this large are counted in parallel instead of streamed
+const PARALLEL_THRESHOLD: u64 = 64 << 20;
+
+const INSTRUCTIONS: [char; 2] = ['(', ')'];
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day01.txt").expect("Could not read file");
+    let path = config::input_path(1);
+
+    // `--floors 3,-2` reports when Santa entered each of those floors
+    let floors: Vec<i64> = config::day_option(1, "floors")
+        .map(|floors| {
+            floors
+                .split(',')
+                .map(|f| f.trim().parse().expect("Expected a list of floors"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let size = fs::metadata(&path).map_or(0, |m| m.len());
+    if floors.is_empty() && size >= PARALLEL_THRESHOLD {
+        let input = trace::in_span("parse", || fs::read(&path).expect("Could not read file"));
+        let (floor, first_basement) =
+            trace::in_span("scan", || scan_parallel(&input, available_threads()));
+        return solution(floor, first_basement);
+    }
+
+    // both parts come out of a single streaming pass over the file
+    let tracker = trace::in_span("track", || {
+        let file = File::open(&path).expect("Could not read file");
+        let mut tracker = floors
+            .iter()
+            .fold(FloorTracker::new(), |tracker, &floor| tracker.watch(floor));
+        tracker.read_from(file).expect("Could not read file");
+        tracker
+    });
+
+    if !floors.is_empty() {
+        println!(
+            "Floors visited: {} to {}",
+            tracker.min_floor(),
+            tracker.max_floor()
+        );
+        for floor in floors {
+            println!("Floor {}: entered at {:?}", floor, tracker.entries(floor));
+        }
+    }
+
+    solution(tracker.floor(), tracker.first_basement())
+}
+
+fn solution(floor: i64, first_basement: Option<usize>) -> SolutionPair {
+    let sol2 = match first_basement {
+        Some(position) => Solution::from(position),
+        None => Solution::from("never"),
+    };
+
+    (Solution::from(floor), sol2)
+}
+
+// Compares the original character counting with the streaming tracker and the
+// chunked scanner on a generated input whose first basement entry sits near
+// the end. `--bench-mib` sets its size.
+pub fn bench() {
+    let mib: usize = config::day_option(1, "bench-mib")
+        .map_or(256, |x| x.parse().expect("Expected a size in MiB"));
+    let input = generate_instructions(mib << 20, 2015);
+    let threads = available_threads();
+
+    println!("\n=== Day 01 · {} MiB of instructions ===", mib);
+    let text = std::str::from_utf8(&input).unwrap();
+    let expected = bench::measure("count_char (baseline)", input.len(), || {
+        (count_floor(text), count_basement(text))
+    });
+    let tracker = bench::measure("FloorTracker::feed", input.len(), || {
+        let mut tracker = FloorTracker::new();
+        tracker.feed(&input);
+        (tracker.floor(), tracker.first_basement())
+    });
+    let single = bench::measure("scan_parallel, 1 thread", input.len(), || {
+        scan_parallel(&input, 1)
+    });
+    let label = format!("scan_parallel, {} thread(s)", threads);
+    let parallel = bench::measure(&label, input.len(), || scan_parallel(&input, threads));
+
+    assert_eq!(expected, tracker);
+    assert_eq!(expected, single);
+    assert_eq!(expected, parallel);
+}
+
+// The original solution, one pass over the input per parenthesis and another
+// to find the basement, kept as the baseline of the benchmark.
+fn count_floor(input: &str) -> i64 {
+    count_char(input, '(') - count_char(input, ')')
+}
+
+fn count_basement(input: &str) -> Option<usize> {
+    let mut floor = 0;
+    for (i, c) in input.chars().enumerate() {
+        if c == '(' {
+            floor += 1;
+        } else if c == ')' {
+            floor -= 1;
+        }
+
+        if floor < 0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
 
-    let sol1: i32 = one(&input);
-    let sol2: i32 = two(&input);
+fn count_char(input: &str, chr: char) -> i64 {
+    let mut count: i64 = 0;
+    for c in input.chars() {
+        if c == chr {
+            count += 1;
+        }
+    }
+    count
+}
+
+// Slightly more `(` than `)` for 90% of the input, then enough `)` to reach the
+// basement, padded with `(` up to `len` bytes.
+fn generate_instructions(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let mut input = Vec::with_capacity(len);
+    let mut floor: i64 = 0;
 
-    (Solution::from(sol1), Solution::from(sol2))
+    while input.len() < len * 9 / 10 {
+        let up = rng.below(100) < 52;
+        input.push(if up { b'(' } else { b')' });
+        floor += if up { 1 } else { -1 };
+    }
+    while floor >= 0 && input.len() < len {
+        input.push(b')');
+        floor -= 1;
+    }
+    input.resize(len, b'(');
+    input
+}
+
+pub fn validate(input: &str) -> Vec<Violation> {
+    validate::check_chars(input, &INSTRUCTIONS)
+}
+
+// `validate` on the input as it is read, the runner's check before solving,
+// so large inputs are never held in memory just to be validated
+pub fn validate_reader(reader: &mut dyn Read) -> io::Result<Vec<Violation>> {
+    let mut checker = validate::CharChecker::new(&INSTRUCTIONS);
+    read_chunks(reader, |chunk| checker.feed(chunk))?;
+    Ok(checker.finish())
 }
 
 #[test]
@@ -36,39 +186,273 @@ fn test() {
     assert_eq!(one(&input8), -3);
     assert_eq!(one(&input9), -3);
 
-    assert_eq!(two(&input10), 1);
-    assert_eq!(two(&input11), 5);
+    assert_eq!(two(&input10), Some(1));
+    assert_eq!(two(&input11), Some(5));
+    assert_eq!(two(&input3), None);
+
+    let mut tracker = FloorTracker::new().watch(2).watch(-1);
+    tracker.feed(b"(()(");
+    tracker.feed(b"()))))(");
+    assert_eq!(tracker.floor(), -1);
+    assert_eq!(tracker.min_floor(), -2);
+    assert_eq!(tracker.max_floor(), 3);
+    assert_eq!(tracker.first_basement(), Some(9));
+    assert_eq!(tracker.entries(2), &[2, 4, 6]);
+    assert_eq!(tracker.entries(-1), &[9, 11]);
+    assert_eq!(tracker.entries(5), &[] as &[usize]);
+
+    let mut streamed = FloorTracker::new().watch(0);
+    streamed.read_from(input9.as_bytes()).unwrap();
+    assert_eq!(streamed.floor(), -3);
+    assert_eq!(streamed.first_basement(), Some(1));
+    assert_eq!(streamed.min_floor(), -3);
+    assert_eq!(streamed.max_floor(), 0);
+
+    // the unwatched blocks find the same basement entry, wherever it falls
+    // within a block and however the input is split
+    let generated = generate_instructions(10_000, 3);
+    let mut watched = FloorTracker::new().watch(i64::MAX);
+    watched.feed(&generated);
+    for size in [1, 63, 64, 1000] {
+        let mut tracker = FloorTracker::new();
+        generated.chunks(size).for_each(|chunk| tracker.feed(chunk));
+        assert_eq!(
+            (tracker.floor(), tracker.first_basement()),
+            (watched.floor(), watched.first_basement())
+        );
+    }
+
+    for input in [&input1, &input5, &input9, &input11] {
+        for threads in 1..=4 {
+            assert_eq!(
+                scan_parallel(input.as_bytes(), threads),
+                (one(input), two(input))
+            );
+        }
+    }
+    let generated = generate_instructions(10_000, 7);
+    let mut tracker = FloorTracker::new();
+    tracker.feed(&generated);
+    assert_eq!(
+        scan_parallel(&generated, 3),
+        (tracker.floor(), tracker.first_basement())
+    );
+    assert!(tracker.first_basement().is_some_and(|p| p > 9_000));
+
+    assert!(validate(&input9).is_empty());
+    assert!(validate("(()\n").is_empty());
+    for input in ["(()\n", "(()\n\n", "", "(é)\r\n)"] {
+        let bytes = input.as_bytes();
+        for size in 1..=3 {
+            let mut checker = validate::CharChecker::new(&INSTRUCTIONS);
+            bytes.chunks(size).for_each(|chunk| checker.feed(chunk));
+            assert_eq!(checker.finish(), validate(input), "{:?}", input);
+        }
+        assert_eq!(validate_reader(&mut &bytes[..]).unwrap(), validate(input));
+    }
+    assert_eq!(
+        validate_reader(&mut &b"(\xff)"[..]).unwrap(),
+        vec![Violation::new(1, 2, "unexpected '\u{fffd}'")]
+    );
+    assert_eq!(
+        validate("(()\n\n"),
+        vec![Violation::new(1, 4, "unexpected '\\n'")]
+    );
+    assert_eq!(validate("").len(), 1);
+    assert_eq!(validate("\n").len(), 1);
 }
 
-fn one(input: &String) -> i32 {
-    count_char(&input, '(') - count_char(&input, ')')
+#[cfg(test)]
+fn one(input: &str) -> i64 {
+    let mut tracker = FloorTracker::new();
+    tracker.feed(input.as_bytes());
+    tracker.floor()
 }
 
-fn two(input: &String) -> i32 {
-    let mut floor = 0;
-    for (i, c) in input.chars().enumerate() {
-        if c == '(' {
-            floor += 1;
-        } else if c == ')' {
-            floor -= 1;
+#[cfg(test)]
+fn two(input: &str) -> Option<usize> {
+    let mut tracker = FloorTracker::new();
+    tracker.feed(input.as_bytes());
+    tracker.first_basement()
+}
+
+// Follows Santa through the building one instruction at a time, so inputs of
+// any size can be streamed in chunks. Positions are 1-based byte offsets into
+// the stream; bytes other than parentheses don't move Santa but still count.
+#[derive(Clone, Debug, Default)]
+struct FloorTracker {
+    floor: i64,
+    position: usize,
+    // the lowest and highest floors, only followed while floors are watched,
+    // the only time they're reported
+    min_floor: i64,
+    max_floor: i64,
+    first_basement: Option<usize>,
+    // floors whose entries are recorded, with the positions they were entered at
+    watched: HashMap<i64, Vec<usize>>,
+}
+
+impl FloorTracker {
+    fn new() -> FloorTracker {
+        FloorTracker::default()
+    }
+
+    // records every position at which Santa steps onto `floor`
+    fn watch(mut self, floor: i64) -> FloorTracker {
+        self.watched.entry(floor).or_default();
+        self
+    }
+
+    // Without watched floors only the floor and the first basement entry are
+    // needed, so a block that can't reach the basement only adds its net
+    // change, which vectorizes.
+    fn feed(&mut self, bytes: &[u8]) {
+        const BLOCK: usize = 64;
+
+        if !self.watched.is_empty() {
+            self.feed_watched(bytes);
+            return;
         }
 
-        if floor < 0 {
-            return (i + 1) as i32;
+        for block in bytes.chunks(BLOCK) {
+            if self.first_basement.is_none() && self.floor < block.len() as i64 {
+                self.first_basement = find_basement(block, self.floor).map(|p| self.position + p);
+            }
+            self.floor += block.iter().map(|&b| step(b)).sum::<i32>() as i64;
+            self.position += block.len();
         }
     }
-    // return only a -1 error code since the inputs can assumed to be good for AoC
-    // alternatively we return a Result and unwrap which will be functionally
-    // the same for these happy inputs
-    -1
+
+    fn feed_watched(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.position += 1;
+            match byte {
+                b'(' => self.floor += 1,
+                b')' => self.floor -= 1,
+                _ => continue,
+            }
+
+            self.min_floor = self.min_floor.min(self.floor);
+            self.max_floor = self.max_floor.max(self.floor);
+
+            if self.floor < 0 && self.first_basement.is_none() {
+                self.first_basement = Some(self.position);
+            }
+
+            if let Some(entries) = self.watched.get_mut(&self.floor) {
+                entries.push(self.position);
+            }
+        }
+    }
+
+    fn read_from(&mut self, reader: impl Read) -> io::Result<()> {
+        read_chunks(reader, |chunk| self.feed(chunk))
+    }
+
+    fn floor(&self) -> i64 {
+        self.floor
+    }
+
+    fn first_basement(&self) -> Option<usize> {
+        self.first_basement
+    }
+
+    fn min_floor(&self) -> i64 {
+        self.min_floor
+    }
+
+    fn max_floor(&self) -> i64 {
+        self.max_floor
+    }
+
+    fn entries(&self, floor: i64) -> &[usize] {
+        self.watched.get(&floor).map_or(&[], Vec::as_slice)
+    }
 }
 
-fn count_char(input: &String, chr: char) -> i32 {
-    let mut count: i32 = 0;
-    for c in input.chars() {
-        if c == chr {
-            count += 1;
+// hands `reader` to `f` 64 KiB at a time
+fn read_chunks(mut reader: impl Read, mut f: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => f(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
         }
     }
-    count
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn step(byte: u8) -> i32 {
+    (byte == b'(') as i32 - (byte == b')') as i32
+}
+
+// The net floor change over a chunk of instructions and the lowest floor
+// reached inside it, both relative to the floor the chunk starts on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct ChunkSummary {
+    net: i64,
+    lowest: i64,
+}
+
+fn summarize(bytes: &[u8]) -> ChunkSummary {
+    const BLOCK: usize = 64;
+
+    let mut summary = ChunkSummary::default();
+    for block in bytes.chunks(BLOCK) {
+        if summary.net - (block.len() as i64) < summary.lowest {
+            // the block could dip below the lowest floor so far, walk it
+            for &byte in block {
+                summary.net += step(byte) as i64;
+                summary.lowest = summary.lowest.min(summary.net);
+            }
+        } else {
+            // it can't, so only its net change matters, which vectorizes
+            summary.net += block.iter().map(|&b| step(b)).sum::<i32>() as i64;
+        }
+    }
+    summary
+}
+
+// 1-based position within `bytes` at which the floor first drops below zero
+fn find_basement(bytes: &[u8], mut floor: i64) -> Option<usize> {
+    bytes
+        .iter()
+        .position(|&byte| {
+            floor += step(byte) as i64;
+            floor < 0
+        })
+        .map(|i| i + 1)
+}
+
+// Splits the input into one chunk per thread and summarizes the chunks in
+// parallel. Chaining the summaries gives the final floor, and the first chunk
+// whose lowest floor goes below zero is the only one walked again to find the
+// exact basement position.
+fn scan_parallel(bytes: &[u8], threads: usize) -> (i64, Option<usize>) {
+    let chunk_len = bytes.len().div_ceil(threads.max(1)).max(1);
+    let summaries: Vec<ChunkSummary> = thread::scope(|scope| {
+        let handles: Vec<_> = bytes
+            .chunks(chunk_len)
+            .map(|chunk| scope.spawn(move || summarize(chunk)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut floor = 0;
+    let mut first_basement = None;
+    for (i, summary) in summaries.iter().enumerate() {
+        if first_basement.is_none() && floor + summary.lowest < 0 {
+            let start = i * chunk_len;
+            let chunk = &bytes[start..(start + chunk_len).min(bytes.len())];
+            first_basement = find_basement(chunk, floor).map(|p| start + p);
+        }
+        floor += summary.net;
+    }
+
+    (floor, first_basement)
 }
@@ -1,55 +1,367 @@
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use std::fs::read_to_string;
+use std::fmt;
+use std::str::FromStr;
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Present {
+    l: u64,
+    w: u64,
+    h: u64,
+}
+
+// why a single `LxWxH` specification was rejected
+#[derive(Clone, Debug, PartialEq)]
+enum DimensionError {
+    Count(usize),
+    NotANumber(String),
+    Zero,
+    Negative(String),
+    // the paper or ribbon of the present doesn't fit in a u64
+    TooLarge,
+}
+
+// a rejected line of the input, 1-based
+#[derive(Clone, Debug, PartialEq)]
+struct PresentError {
+    line: usize,
+    text: String,
+    error: DimensionError,
+}
+
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day02.txt").expect("Could not read file");
-    calculate(&input)
+    let presents = trace::in_span("parse", || {
+        parse_presents(&config::read_input(2)).unwrap_or_else(|e| panic!("{}", e))
+    });
+
+    // `--report N` lists every present and the N most expensive ones, ranked by
+    // `--rank paper` (the default) or `--rank ribbon`
+    if let Some(top) = config::day_option(2, "report") {
+        let top = top.parse().expect("Expected a number of presents");
+        let rank = config::day_option(2, "rank").unwrap_or("paper");
+        report(&presents, top, rank);
+    }
+
+    let sol1: u64 = trace::in_span("part1", || presents.iter().map(Present::paper).sum());
+    let sol2: u64 = trace::in_span("part2", || presents.iter().map(Present::ribbon).sum());
+
+    (Solution::from(sol1), Solution::from(sol2))
+}
+
+pub fn validate(input: &str) -> Vec<Violation> {
+    validate::check_lines(input, |line| {
+        let error = match line.parse::<Present>() {
+            Ok(_) => return Ok(()),
+            Err(error) => error,
+        };
+
+        // sides are parsed in order, so the first bad one is the culprit, and
+        // a present too large as a whole is pointed at from its start
+        let mut column = 1;
+        if !matches!(error, DimensionError::Count(_)) {
+            let mut start = 1;
+            for side in line.split('x') {
+                if parse_side(side).is_err() {
+                    column = start;
+                    break;
+                }
+                start += side.len() + 1;
+            }
+        }
+        Err((column, error.to_string()))
+    })
 }
 
 #[test]
 fn test() {
+    use std::fs::read_to_string;
+
     let input: String = read_to_string("input/test/day02.txt").expect("Could not read file");
 
     let (test_sol1, test_sol2) = calculate(&input);
 
     assert_eq!(test_sol1, Solution::U64(101));
     assert_eq!(test_sol2, Solution::U64(48));
+
+    let present: Present = "2x3x4".parse().unwrap();
+    assert_eq!(present, Present { l: 2, w: 3, h: 4 });
+    assert_eq!(present.surface_area(), 52);
+    assert_eq!(present.smallest_face(), 6);
+    assert_eq!(present.smallest_perimeter(), 10);
+    assert_eq!(present.volume(), 24);
+    assert_eq!(present.paper(), 58);
+    assert_eq!(present.ribbon(), 34);
+
+    let present: Present = "1x1x10".parse().unwrap();
+    assert_eq!(present.paper(), 43);
+    assert_eq!(present.ribbon(), 14);
+
+    // every ordering of the same box costs the same, ties included
+    let boxes = [
+        ((2, 3, 4), 58, 34),
+        ((1, 1, 10), 43, 14),
+        ((2, 2, 3), 36, 20),
+        ((3, 3, 3), 63, 39),
+        ((1, 2, 2), 18, 10),
+        ((1, 1, 1), 7, 5),
+    ];
+    for ((a, b, c), paper, ribbon) in boxes {
+        let permutations = [
+            (a, b, c),
+            (a, c, b),
+            (b, a, c),
+            (b, c, a),
+            (c, a, b),
+            (c, b, a),
+        ];
+        for (l, w, h) in permutations {
+            let present = Present { l, w, h };
+            assert_eq!(present.paper(), paper, "{:?}", present);
+            assert_eq!(present.ribbon(), ribbon, "{:?}", present);
+        }
+    }
+
+    assert_eq!("2x3".parse::<Present>(), Err(DimensionError::Count(2)));
+    assert_eq!("2x3x4x5".parse::<Present>(), Err(DimensionError::Count(4)));
+    assert_eq!(
+        "2x3xa".parse::<Present>(),
+        Err(DimensionError::NotANumber("a".to_string()))
+    );
+    assert_eq!(
+        "2xx4".parse::<Present>(),
+        Err(DimensionError::NotANumber("".to_string()))
+    );
+    assert_eq!("2x0x4".parse::<Present>(), Err(DimensionError::Zero));
+    assert_eq!(
+        "2x-3x4".parse::<Present>(),
+        Err(DimensionError::Negative("-3".to_string()))
+    );
+    assert_eq!(
+        parse_presents("1x1x1\n2x0x4\n"),
+        Err(PresentError {
+            line: 2,
+            text: "2x0x4".to_string(),
+            error: DimensionError::Zero,
+        })
+    );
+
+    let presents = parse_presents("1x1x1\n2x3x4\n1x1x10\n").unwrap();
+    let ranked = most_expensive(&presents, 2, Present::paper);
+    assert_eq!(ranked, vec![presents[1], presents[2]]);
+
+    assert!(validate(&input).is_empty());
+    assert_eq!(
+        validate("2x3x4\n1x1\n2xax4\n"),
+        vec![
+            Violation::new(2, 1, "expected 3 dimensions, found 2"),
+            Violation::new(3, 3, "expected a number, found \"a\""),
+        ]
+    );
+    assert_eq!(
+        validate("2x3x00\n2x-3x4\n+2x3x4\n2x3x-\n"),
+        vec![
+            Violation::new(1, 5, "dimensions must be positive, found 0"),
+            Violation::new(2, 3, "dimensions must be positive, found -3"),
+            Violation::new(3, 1, "expected a number, found \"+2\""),
+            Violation::new(4, 5, "expected a number, found \"-\""),
+        ]
+    );
+
+    // the paper and ribbon of every valid present fit in a u64
+    assert_eq!(
+        "4294967296x4294967296x1".parse::<Present>(),
+        Err(DimensionError::TooLarge)
+    );
+    assert_eq!(
+        "1x1x18446744073709551616".parse::<Present>(),
+        Err(DimensionError::TooLarge)
+    );
+    assert!("1x1x4294967296".parse::<Present>().is_ok());
+    assert_eq!(
+        validate("4294967296x4294967296x1\n1x99999999999999999999x1\n"),
+        vec![
+            Violation::new(1, 1, "dimensions too large to wrap"),
+            Violation::new(2, 3, "dimensions too large to wrap"),
+        ]
+    );
+}
+
+#[cfg(test)]
+fn calculate(input: &str) -> SolutionPair {
+    let presents = parse_presents(input).unwrap();
+    let total_paper: u64 = presents.iter().map(Present::paper).sum();
+    let total_ribbon: u64 = presents.iter().map(Present::ribbon).sum();
+
+    (Solution::U64(total_paper), Solution::U64(total_ribbon))
 }
 
-fn calculate(input: &String) -> SolutionPair {
-    let mut total_paper: u64 = 0;
-    let mut total_ribbon: u64 = 0;
-    for line in input.lines() {
-        let side_lengths = line.split("x").collect::<Vec<&str>>();
-        let l = side_lengths[0].parse::<u64>().unwrap();
-        let w = side_lengths[1].parse::<u64>().unwrap();
-        let h = side_lengths[2].parse::<u64>().unwrap();
-
-        let lw = l * w;
-        let wh = w * h;
-        let hl = h * l;
-
-        let smallest_perimeter: u64;
-
-        let smallest_side = if lw < wh && lw < hl {
-            smallest_perimeter = 2 * (l + w);
-            lw
-        } else if wh < hl {
-            smallest_perimeter = 2 * (w + h);
-            wh
-        } else {
-            smallest_perimeter = 2 * (h + l);
-            hl
+fn parse_presents(input: &str) -> Result<Vec<Present>, PresentError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse().map_err(|error| PresentError {
+                line: i + 1,
+                text: line.to_string(),
+                error,
+            })
+        })
+        .collect()
+}
+
+// the `top` presents needing the most of `cost`, most expensive first
+fn most_expensive(presents: &[Present], top: usize, cost: fn(&Present) -> u64) -> Vec<Present> {
+    let mut ranked = presents.to_vec();
+    ranked.sort_by_key(|present| std::cmp::Reverse(cost(present)));
+    ranked.truncate(top);
+    ranked
+}
+
+fn report(presents: &[Present], top: usize, rank: &str) {
+    let cost: fn(&Present) -> u64 = match rank {
+        "paper" => Present::paper,
+        "ribbon" => Present::ribbon,
+        _ => panic!("Unknown ranking: {} (expected paper or ribbon)", rank),
+    };
+
+    println!(
+        "{:>12} {:>8} {:>8} {:>8}",
+        "present", "paper", "ribbon", "volume"
+    );
+    for present in presents {
+        print_present(present);
+    }
+
+    println!("\nTop {} by {}:", top, rank);
+    for present in most_expensive(presents, top, cost) {
+        print_present(&present);
+    }
+}
+
+fn print_present(present: &Present) {
+    println!(
+        "{:>12} {:>8} {:>8} {:>8}",
+        format!("{}x{}x{}", present.l, present.w, present.h),
+        present.paper(),
+        present.ribbon(),
+        present.volume()
+    );
+}
+
+impl FromStr for Present {
+    type Err = DimensionError;
+
+    fn from_str(string: &str) -> Result<Present, DimensionError> {
+        let side_lengths = string.split('x').collect::<Vec<&str>>();
+        if side_lengths.len() != 3 {
+            return Err(DimensionError::Count(side_lengths.len()));
+        }
+
+        let present = Present {
+            l: parse_side(side_lengths[0])?,
+            w: parse_side(side_lengths[1])?,
+            h: parse_side(side_lengths[2])?,
         };
+        if present.checked_paper().is_none() || present.checked_ribbon().is_none() {
+            return Err(DimensionError::TooLarge);
+        }
+        Ok(present)
+    }
+}
 
-        total_paper += 2 * (lw + wh + hl);
-        total_paper += smallest_side;
+// digits only, so `+2` is not a number and `-2` a negative one
+fn parse_side(side: &str) -> Result<u64, DimensionError> {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if side.strip_prefix('-').is_some_and(digits) {
+        return Err(DimensionError::Negative(side.to_string()));
+    }
+    if !digits(side) {
+        return Err(DimensionError::NotANumber(side.to_string()));
+    }
+    match side.parse::<u64>() {
+        Ok(0) => Err(DimensionError::Zero),
+        Ok(n) => Ok(n),
+        Err(_) => Err(DimensionError::TooLarge),
+    }
+}
+
+impl fmt::Display for DimensionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DimensionError::Count(n) => write!(f, "expected 3 dimensions, found {}", n),
+            DimensionError::NotANumber(side) => write!(f, "expected a number, found {:?}", side),
+            DimensionError::Zero => write!(f, "dimensions must be positive, found 0"),
+            DimensionError::Negative(side) => {
+                write!(f, "dimensions must be positive, found {}", side)
+            }
+            DimensionError::TooLarge => write!(f, "dimensions too large to wrap"),
+        }
+    }
+}
 
-        total_ribbon += smallest_perimeter;
-        total_ribbon += l * w * h;
+impl fmt::Display for PresentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {} ({:?}): {}", self.line, self.text, self.error)
     }
+}
 
-    (Solution::U64(total_paper), Solution::U64(total_ribbon))
+impl Present {
+    fn surface_area(&self) -> u64 {
+        2 * (self.l * self.w + self.w * self.h + self.h * self.l)
+    }
+
+    // the smallest face is always spanned by the two shortest sides
+    fn smallest_face(&self) -> u64 {
+        let [a, b, _] = self.sorted_sides();
+        a * b
+    }
+
+    fn smallest_perimeter(&self) -> u64 {
+        let [a, b, _] = self.sorted_sides();
+        2 * (a + b)
+    }
+
+    fn volume(&self) -> u64 {
+        self.l * self.w * self.h
+    }
+
+    // wrapping paper: the whole surface plus slack the size of the smallest face
+    fn paper(&self) -> u64 {
+        self.surface_area() + self.smallest_face()
+    }
+
+    // ribbon: once around the smallest perimeter plus a bow as long as the volume
+    fn ribbon(&self) -> u64 {
+        self.smallest_perimeter() + self.volume()
+    }
+
+    // `paper` and `ribbon`, or None if they overflow, checked once when the
+    // present is parsed
+    fn checked_paper(&self) -> Option<u64> {
+        let [a, b, _] = self.sorted_sides();
+        let faces = [
+            self.l.checked_mul(self.w)?,
+            self.w.checked_mul(self.h)?,
+            self.h.checked_mul(self.l)?,
+        ];
+        let area = faces
+            .iter()
+            .try_fold(0u64, |sum, &face| sum.checked_add(face))?;
+        area.checked_mul(2)?.checked_add(a.checked_mul(b)?)
+    }
+
+    fn checked_ribbon(&self) -> Option<u64> {
+        let [a, b, _] = self.sorted_sides();
+        let volume = self.l.checked_mul(self.w)?.checked_mul(self.h)?;
+        a.checked_add(b)?.checked_mul(2)?.checked_add(volume)
+    }
+
+    fn sorted_sides(&self) -> [u64; 3] {
+        let mut sides = [self.l, self.w, self.h];
+        sides.sort_unstable();
+        sides
+    }
 }
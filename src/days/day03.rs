@@ -1,88 +1,384 @@
+use crate::etc::image::{self, Rgb};
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use std::{collections::HashMap, fs::read_to_string};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+// heatmap characters for 0, 1, 2, 3-4, 5-8, 9-16, ... presents
+const HEAT: &[u8] = b" .:-=+*#%@";
+
+// mover colors in the rendered image, repeating after the sixth mover
+const PALETTE: [Rgb; 6] = [
+    [230, 57, 70],
+    [69, 123, 237],
+    [82, 183, 136],
+    [255, 183, 3],
+    [181, 101, 222],
+    [72, 202, 228],
+];
 
 ///////////////////////////////////////////////////////////////////////////////
 
+type House = (i64, i64);
+
+// Which mover follows each instruction. A custom schedule is a repeating
+// pattern of mover indices, so `[0, 1, 1]` gives the second mover two moves
+// for every move of the first.
+#[derive(Clone, Debug, PartialEq)]
+enum Schedule {
+    RoundRobin,
+    Custom(Vec<usize>),
+}
+
+#[derive(Clone, Debug, Default)]
+struct Mover {
+    position: House,
+    visited: HashSet<House>,
+}
+
+// Walks any number of movers over the grid of houses, each delivering a
+// present to every house it stops at, including the one it starts on.
+#[derive(Clone, Debug)]
+struct Walker {
+    movers: Vec<Mover>,
+    schedule: Schedule,
+    counts: HashMap<House, u64>,
+    steps: usize,
+}
+
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day03.txt").expect("Could not read file");
+    let input = trace::in_span("parse", || config::read_input(3));
+    driver(&input)
+}
+
+fn driver(input: &str) -> SolutionPair {
+    // the validator accepts a single trailing newline, which isn't a direction
+    let input = input.strip_suffix('\n').unwrap_or(input);
+
+    let sol1 = trace::in_span("part1", || {
+        let mut walker = Walker::new(1, Schedule::RoundRobin);
+        walker.walk(input);
+        walker.houses()
+    });
+    let sol2 = trace::in_span("part2", || {
+        let mut walker = Walker::new(2, Schedule::RoundRobin);
+        walker.walk(input);
+        walker.houses()
+    });
+
+    // `--movers N` and/or `--schedule 0,1,1` report on a walk with that crew,
+    // `--at-least K` (default 2) counts the houses that got K or more presents
+    let crew = crew();
+    if let Some((movers, schedule)) = &crew {
+        let at_least = config::day_option(3, "at-least")
+            .map_or(2, |k| k.parse().expect("Expected a number of presents"));
 
-    let (sol1, _) = house_delivery_counts(&input, &mut HashMap::new());
-    let (santa, robo) = split_directions(&input);
-    let map = &mut HashMap::new();
-    let (_, map) = house_delivery_counts(&santa, map);
-    let (sol2, _) = house_delivery_counts(&robo, map);
+        let mut walker = Walker::new(*movers, schedule.clone());
+        walker.walk(input);
+        report(&walker, at_least);
+    }
+
+    // `--render out.png` (or `.ppm`) draws the route of that crew, or of Santa
+    // and Robo-Santa, as an image; any other path gets an ASCII heatmap and
+    // `-` prints it
+    if let Some(path) = config::day_option(3, "render") {
+        let (movers, schedule) = crew.unwrap_or((2, Schedule::RoundRobin));
+        let mut walker = Walker::new(movers, schedule);
+        walker.walk(input);
+        render(&walker, path);
+    }
 
     (Solution::from(sol1), Solution::from(sol2))
 }
 
+// the movers and schedule given through `--movers` and `--schedule`, if any
+fn crew() -> Option<(usize, Schedule)> {
+    let movers = config::day_option(3, "movers");
+    let schedule = config::day_option(3, "schedule");
+    if movers.is_none() && schedule.is_none() {
+        return None;
+    }
+
+    let schedule = match schedule {
+        Some(s) => Schedule::Custom(
+            s.split(',')
+                .map(|i| i.trim().parse().expect("Expected a list of movers"))
+                .collect(),
+        ),
+        None => Schedule::RoundRobin,
+    };
+    let movers = match movers {
+        Some(n) => n.parse().expect("Expected a number of movers"),
+        None => schedule.movers_needed(),
+    };
+    Some((movers, schedule))
+}
+
+fn render(walker: &Walker, path: &str) {
+    if path.ends_with(".ppm") || path.ends_with(".png") {
+        let (width, height, pixels) = walker.image();
+        let write = if path.ends_with(".png") {
+            image::write_rgb_png
+        } else {
+            image::write_ppm
+        };
+        write(path, width, height, &pixels)
+            .unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+        println!("Route written to {} ({}x{})", path, width, height);
+    } else if path == "-" {
+        print!("{}", walker.heatmap());
+    } else {
+        fs::write(path, walker.heatmap())
+            .unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+        println!("Heatmap written to {}", path);
+    }
+}
+
+fn report(walker: &Walker, at_least: u64) {
+    let ((min_x, min_y), (max_x, max_y)) = walker.bounding_box();
+    println!("Houses visited: {}", walker.houses());
+    println!("Starting house: {} presents", walker.visits((0, 0)));
+    for mover in 0..walker.movers.len() {
+        println!("Mover {}: {} houses", mover, walker.visited_by(mover).len());
+    }
+    if let Some(((x, y), count)) = walker.most_visited() {
+        println!("Most visited: ({}, {}) with {} presents", x, y, count);
+    }
+    println!(
+        "Bounding box: ({}, {}) to ({}, {})",
+        min_x, min_y, max_x, max_y
+    );
+    println!(
+        "Houses with at least {} presents: {}",
+        at_least,
+        walker.houses_with_at_least(at_least)
+    );
+}
+
+pub fn validate(input: &str) -> Vec<Violation> {
+    validate::check_chars(input, &['^', 'v', '<', '>'])
+}
+
 #[test]
 fn test() {
-    let input1 = "^v".to_string();
-    let input2 = "^>v<".to_string();
-    let input3 = "^v^v^v^v^v".to_string();
-
-    let (sol1, _) = house_delivery_counts(&">".to_string(), &mut HashMap::new());
-    let (sol2, _) = house_delivery_counts(&input2, &mut HashMap::new());
-    let (sol3, _) = house_delivery_counts(&input3, &mut HashMap::new());
-
-    let (santa1, robo1) = split_directions(&input1);
-    let (santa2, robo2) = split_directions(&input2);
-    let (santa3, robo3) = split_directions(&input3);
-
-    let map1 = &mut HashMap::new();
-    let map2 = &mut HashMap::new();
-    let map3 = &mut HashMap::new();
-
-    let (_, map1) = house_delivery_counts(&santa1, map1);
-    let (sol4, _) = house_delivery_counts(&robo1, map1);
-    let (_, map2) = house_delivery_counts(&santa2, map2);
-    let (sol5, _) = house_delivery_counts(&robo2, map2);
-    let (_, map3) = house_delivery_counts(&santa3, map3);
-    let (sol6, _) = house_delivery_counts(&robo3, map3);
-
-    assert_eq!(sol1, 2);
-    assert_eq!(sol2, 4);
-    assert_eq!(sol3, 2);
-    assert_eq!(sol4, 3);
-    assert_eq!(sol5, 3);
-    assert_eq!(sol6, 11);
+    let houses = |movers: usize, directions: &str| {
+        let mut walker = Walker::new(movers, Schedule::RoundRobin);
+        walker.walk(directions);
+        walker.houses()
+    };
+
+    assert_eq!(houses(1, ">"), 2);
+    assert_eq!(houses(1, "^>v<"), 4);
+    assert_eq!(houses(1, "^v^v^v^v^v"), 2);
+    assert_eq!(houses(2, "^v"), 3);
+    assert_eq!(houses(2, "^>v<"), 3);
+    assert_eq!(houses(2, "^v^v^v^v^v"), 11);
+    assert_eq!(
+        driver("^>v<\n"),
+        (
+            Solution::from(houses(1, "^>v<")),
+            Solution::from(houses(2, "^>v<"))
+        )
+    );
+
+    // walking in pieces continues the schedule where it left off
+    let mut walker = Walker::new(2, Schedule::RoundRobin);
+    walker.walk("^v^v^");
+    walker.walk("v^v^v");
+    assert_eq!(walker.houses(), 11);
+    assert_eq!(walker.visits((0, 0)), 2);
+    assert_eq!(walker.visited_by(0).len(), 6);
+    assert_eq!(walker.bounding_box(), ((0, -5), (0, 5)));
+
+    let mut walker = Walker::new(1, Schedule::RoundRobin);
+    walker.walk("^v^v^v^v^v");
+    assert_eq!(walker.visits((0, 0)), 6);
+    assert_eq!(walker.visits((0, 1)), 5);
+    assert_eq!(walker.visits((1, 0)), 0);
+    assert_eq!(walker.most_visited(), Some(((0, 0), 6)));
+    assert_eq!(walker.houses_with_at_least(6), 1);
+    assert_eq!(walker.houses_with_at_least(5), 2);
+
+    // the first mover goes up once for every two moves the second makes right
+    let mut walker = Walker::new(2, Schedule::Custom(vec![0, 1, 1]));
+    walker.walk("^>>^>>");
+    assert_eq!(walker.visited_by(0).len(), 3);
+    assert_eq!(walker.visited_by(1).len(), 5);
+    assert_eq!(walker.bounding_box(), ((0, 0), (4, 2)));
+    assert_eq!(walker.houses(), 7);
+    assert_eq!(Schedule::Custom(vec![0, 3]).movers_needed(), 4);
+
+    let mut walker = Walker::new(3, Schedule::RoundRobin);
+    walker.walk("><^");
+    assert_eq!(walker.houses(), 4);
+    assert_eq!(walker.most_visited(), Some(((0, 0), 3)));
+
+    let mut walker = Walker::new(2, Schedule::RoundRobin);
+    walker.walk("^>>>");
+    assert_eq!(walker.heatmap(), ".. \n:..\n");
+    let (width, height, pixels) = walker.image();
+    assert_eq!((width, height), (3, 2));
+    // Santa alone at the top left, only Robo-Santa at the bottom right
+    assert_eq!(pixels[0], [115, 29, 35]);
+    assert_eq!(pixels[2], [0, 0, 0]);
+    assert_eq!(pixels[5], [35, 62, 119]);
+    // both at the origin, which has the most presents and is the brightest
+    assert!(pixels[3][0] > 0 && pixels[3][2] > 0);
+    assert!(pixels[3].iter().max() > pixels[0].iter().max());
+
+    assert!(validate("^v^v^v^v^v").is_empty());
+    assert_eq!(
+        validate("^>\r\n"),
+        vec![Violation::new(1, 3, "unexpected '\\r'")]
+    );
 }
 
-fn house_delivery_counts<'a>(
-    directions: &String,
-    map: &'a mut HashMap<(i64, i64), bool>,
-) -> (u64, &'a mut HashMap<(i64, i64), bool>) {
-    let mut x: i64 = 0;
-    let mut y: i64 = 0;
-
-    let _house = map.entry((x, y)).or_insert(true);
-
-    for dir in directions.chars() {
-        match dir {
-            '>' => x += 1,
-            '<' => x -= 1,
-            '^' => y += 1,
-            'v' => y -= 1,
-            _ => panic!("Invalid direction"),
+impl Schedule {
+    fn mover(&self, step: usize, movers: usize) -> usize {
+        match self {
+            Schedule::RoundRobin => step % movers,
+            Schedule::Custom(pattern) => pattern[step % pattern.len()],
         }
-
-        let _house = map.entry((x, y)).or_insert(true);
     }
 
-    (map.keys().len() as u64, map)
+    // the smallest crew that can follow the schedule
+    fn movers_needed(&self) -> usize {
+        match self {
+            Schedule::RoundRobin => 1,
+            Schedule::Custom(pattern) => pattern.iter().max().map_or(1, |m| m + 1),
+        }
+    }
 }
 
-fn split_directions(directions: &String) -> (String, String) {
-    let mut santa_directions = String::new();
-    let mut robo_santa_directions = String::new();
+impl Walker {
+    fn new(movers: usize, schedule: Schedule) -> Walker {
+        assert!(movers > 0, "A walk needs at least one mover");
+        if let Schedule::Custom(pattern) = &schedule {
+            assert!(!pattern.is_empty(), "A custom schedule can't be empty");
+            assert!(
+                schedule.movers_needed() <= movers,
+                "The schedule refers to more than {} movers",
+                movers
+            );
+        }
 
-    for (i, dir) in directions.chars().enumerate() {
-        if i % 2 == 0 {
-            santa_directions.push(dir);
-        } else {
-            robo_santa_directions.push(dir);
+        let start = Mover {
+            position: (0, 0),
+            visited: HashSet::from([(0, 0)]),
+        };
+        Walker {
+            movers: vec![start; movers],
+            schedule,
+            counts: HashMap::from([((0, 0), movers as u64)]),
+            steps: 0,
         }
     }
 
-    (santa_directions, robo_santa_directions)
+    fn walk(&mut self, directions: &str) {
+        for dir in directions.chars() {
+            let (dx, dy) = match dir {
+                '>' => (1, 0),
+                '<' => (-1, 0),
+                '^' => (0, 1),
+                'v' => (0, -1),
+                _ => panic!("Invalid direction"),
+            };
+
+            let index = self.schedule.mover(self.steps, self.movers.len());
+            let mover = &mut self.movers[index];
+            mover.position = (mover.position.0 + dx, mover.position.1 + dy);
+            mover.visited.insert(mover.position);
+            *self.counts.entry(mover.position).or_default() += 1;
+            self.steps += 1;
+        }
+    }
+
+    // houses that received at least one present
+    fn houses(&self) -> usize {
+        self.counts.len()
+    }
+
+    fn visits(&self, house: House) -> u64 {
+        self.counts.get(&house).copied().unwrap_or(0)
+    }
+
+    fn visited_by(&self, mover: usize) -> &HashSet<House> {
+        &self.movers[mover].visited
+    }
+
+    // ties go to the house closest to the bottom left
+    fn most_visited(&self) -> Option<(House, u64)> {
+        self.counts
+            .iter()
+            .map(|(&house, &count)| (house, count))
+            .max_by_key(|&((x, y), count)| (count, -y, -x))
+    }
+
+    fn bounding_box(&self) -> (House, House) {
+        self.counts
+            .keys()
+            .fold(((0, 0), (0, 0)), |(min, max), &(x, y)| {
+                ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+            })
+    }
+
+    fn houses_with_at_least(&self, presents: u64) -> usize {
+        self.counts.values().filter(|&&c| c >= presents).count()
+    }
+
+    // one character per house, top row first, darker to brighter by presents
+    fn heatmap(&self) -> String {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounding_box();
+        let mut map = String::new();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                map.push(HEAT[heat_level(self.visits((x, y)), HEAT.len() - 1)] as char);
+            }
+            map.push('\n');
+        }
+        map
+    }
+
+    // Width, height and pixels of the route. A house is colored by the average
+    // of the colors of the movers that visited it, brighter the more presents
+    // it got, and black if nobody stopped there.
+    fn image(&self) -> (usize, usize, Vec<Rgb>) {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounding_box();
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        let top = heat_level(self.most_visited().map_or(1, |(_, c)| c), usize::MAX);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                let visitors: Vec<Rgb> = (0..self.movers.len())
+                    .filter(|&m| self.visited_by(m).contains(&(x, y)))
+                    .map(|m| PALETTE[m % PALETTE.len()])
+                    .collect();
+                if visitors.is_empty() {
+                    pixels.push([0, 0, 0]);
+                    continue;
+                }
+
+                // from half brightness for a single present up to full
+                let level = heat_level(self.visits((x, y)), usize::MAX);
+                let scale = 0.5 + 0.5 * (level - 1) as f64 / top.max(2).saturating_sub(1) as f64;
+                pixels.push(std::array::from_fn(|i| {
+                    let sum: u32 = visitors.iter().map(|c| c[i] as u32).sum();
+                    (sum as f64 / visitors.len() as f64 * scale).ceil() as u8
+                }));
+            }
+        }
+        (width, height, pixels)
+    }
+}
+
+// 0 for no presents, then one level per doubling (1, 2, 3-4, 5-8, ...)
+fn heat_level(presents: u64, max: usize) -> usize {
+    let level = match presents {
+        0 => 0,
+        n => 1 + (64 - (n - 1).leading_zeros()) as usize,
+    };
+    level.min(max)
 }
@@ -1,32 +1,68 @@
+use crate::etc::miner::{Algorithm, Miner, Pattern};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub fn solve() -> SolutionPair {
-    // Your solution here...
-    let sol1: u64 = worker(&"ckczppom".to_string(), 5);
-    let sol2: u64 = worker(&"ckczppom".to_string(), 6);
+    let key = config::day_option(4, "key").unwrap_or("ckczppom");
+
+    let sol1: u64 = trace::in_span("part1", || worker(key, 5));
+    let sol2: u64 = trace::in_span("part2", || worker(key, 6));
+
+    // `--pattern 000abc` (or `0b...` for bits) also mines that prefix and
+    // prints the first `--matches` counters (default 1), searching from
+    // `--start` in steps of `--step` with `--algorithm` (md5 by default)
+    if let Some(pattern) = config::day_option(4, "pattern") {
+        let option = |name, default: u64| {
+            config::day_option(4, name).map_or(default, |x| {
+                x.parse()
+                    .unwrap_or_else(|_| panic!("Expected a number for {}", name))
+            })
+        };
+        let pattern: Pattern = pattern.parse().unwrap_or_else(|e| panic!("{}", e));
+        let algorithm: Algorithm = config::day_option(4, "algorithm")
+            .unwrap_or("md5")
+            .parse()
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let miner = miner(key, pattern)
+            .start(option("start", 1))
+            .step(option("step", 1));
+        let matches = trace::in_span("mine", || {
+            miner.mine(algorithm, option("matches", 1) as usize)
+        });
+        for counter in matches {
+            println!("{}{}", key, counter);
+        }
+    }
 
     (Solution::from(sol1), Solution::from(sol2))
 }
 
 #[test]
 fn test() {
-    assert_eq!(worker(&"abcdef".to_string(), 5), 609043);
-    assert_eq!(worker(&"pqrstuv".to_string(), 5), 1048970);
+    assert_eq!(worker("abcdef", 5), 609043);
+    assert_eq!(worker("pqrstuv", 5), 1048970);
 }
 
-fn worker(key: &String, pow: usize) -> u64 {
-    let target_string = "0".repeat(pow);
-
-    // check if already a valid key and hash
-    let mut _result = format!("{:x}", md5::compute(key));
+// the smallest positive counter whose MD5 hash, appended to `key`, starts with
+// `zeros` zero hex digits
+fn worker(key: &str, zeros: usize) -> u64 {
+    miner(key, Pattern::zeros(zeros)).mine(Algorithm::Md5, 1)[0]
+}
 
-    // search for the addon which has proof of work
-    let mut counter: u64 = 0;
-    while _result.chars().take(pow).collect::<String>() != target_string {
-        counter += 1;
-        _result = format!("{:x}", md5::compute(format!("{}{}", key, counter)));
+// `--threads N` limits the search threads, all available ones by default.
+// `--progress` prints the hash rate to stderr and `--checkpoint state.txt`
+// saves the search there, so an interrupted search resumes when run again.
+fn miner(key: &str, pattern: Pattern) -> Miner {
+    let mut miner = Miner::new(key, pattern)
+        .progress(config::day_option(4, "progress").is_some_and(|x| x != "false"));
+    if let Some(n) = config::day_option(4, "threads") {
+        miner = miner.threads(n.parse().expect("Expected a number of threads"));
+    }
+    if let Some(path) = config::day_option(4, "checkpoint") {
+        miner = miner.checkpoint(path);
     }
-    counter
+    miner
 }
@@ -1,135 +1,526 @@
+use crate::etc::bench::{self, Rng};
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use std::fs::read_to_string;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// The two policies of the puzzle, in the policy language of `Policy::parse`
+const PART1_POLICY: &str = "at-least 3 of aeiou; gap-repeat 0; forbid ab cd pq xy";
+const PART2_POLICY: &str = "repeated-pair; gap-repeat 1";
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day05.txt").expect("couldn't read input file");
+    let input = trace::in_span("parse", || config::read_input(5));
+    let part1 = Policy::parse(PART1_POLICY).unwrap();
+    let part2 = Policy::parse(PART2_POLICY).unwrap();
 
-    let mut nice: u64 = 0;
-    let mut new_nice: u64 = 0;
-    for line in input.lines() {
-        if is_nice_string(&line.to_owned()) {
-            nice += 1;
+    // `--policy "forbid ab; gap-repeat 2"` counts the strings nice under a
+    // policy of your own, and `--explain abcd` says why a string is naughty
+    let custom = config::day_option(5, "policy")
+        .map(|policy| Policy::parse(policy).unwrap_or_else(|e| panic!("{}", e)));
+    if let Some(policy) = &custom {
+        let nice = input.lines().filter(|line| policy.is_nice(line)).count();
+        println!(
+            "Nice under {:?}: {}",
+            config::day_option(5, "policy").unwrap(),
+            nice
+        );
+    }
+    if let Some(string) = config::day_option(5, "explain") {
+        let policies = [
+            ("part 1", Some(&part1)),
+            ("part 2", Some(&part2)),
+            ("policy", custom.as_ref()),
+        ];
+        for (name, policy) in policies {
+            if let Some(policy) = policy {
+                explain(name, policy, string);
+            }
         }
+    }
+
+    // both policies are decided in a single pass over each line
+    let (nice, new_nice) = trace::in_span("classify", || count_nice(&input));
+
+    (Solution::from(nice), Solution::from(new_nice))
+}
+
+fn count_nice(input: &str) -> (usize, usize) {
+    let mut classifier = Classifier::new();
+    input.lines().fold((0, 0), |(nice, new_nice), line| {
+        let (part1, part2) = classifier.classify(line);
+        (nice + part1 as usize, new_nice + part2 as usize)
+    })
+}
 
-        if is_new_nice_string(&line.to_owned()) {
-            new_nice += 1;
+// Compares the rule engine with the single-pass classifier on random lines of
+// lowercase letters. `--bench-lines` sets how many (default 1,000,000).
+pub fn bench() {
+    let lines: usize = config::day_option(5, "bench-lines").map_or(1_000_000, |x| {
+        x.parse().expect("Expected a number of lines")
+    });
+    let input = generate_strings(lines, 16, 2015);
+    let part1 = Policy::parse(PART1_POLICY).unwrap();
+    let part2 = Policy::parse(PART2_POLICY).unwrap();
+
+    println!("\n=== Day 05 · {} lines ===", lines);
+    let expected = bench::measure("Policy::is_nice", input.len(), || {
+        let nice = input.lines().filter(|line| part1.is_nice(line)).count();
+        let new_nice = input.lines().filter(|line| part2.is_nice(line)).count();
+        (nice, new_nice)
+    });
+    let classified = bench::measure("Classifier::classify", input.len(), || count_nice(&input));
+
+    assert_eq!(expected, classified);
+}
+
+// `lines` random lines of `len` lowercase letters, drawn mostly from the first
+// half of the alphabet so that both policies see a fair share of nice strings
+fn generate_strings(lines: usize, len: usize, seed: u64) -> String {
+    let mut rng = Rng::new(seed);
+    let mut input = String::with_capacity(lines * (len + 1));
+    for _ in 0..lines {
+        for _ in 0..len {
+            let letters = if rng.below(4) == 0 { 26 } else { 8 };
+            input.push((b'a' + rng.below(letters) as u8) as char);
         }
+        input.push('\n');
     }
+    input
+}
 
-    (Solution::from(nice), Solution::from(new_nice))
+fn explain(name: &str, policy: &Policy, string: &str) {
+    let reasons = policy.explain(string);
+    if reasons.is_empty() {
+        println!("{:?} is nice under {}", string, name);
+    }
+    for reason in reasons {
+        println!("{:?} is naughty under {}: {}", string, name, reason);
+    }
+}
+
+pub fn validate(input: &str) -> Vec<Violation> {
+    validate::check_lines(input, |line| {
+        match line.chars().position(|c| !c.is_ascii_lowercase()) {
+            Some(i) => Err((i + 1, "expected a lowercase letter".to_string())),
+            None => Ok(()),
+        }
+    })
 }
 
 #[test]
 fn test() {
-    assert_eq!(has_forbidden_strings(&"adwytgowhe".to_string()), false);
-    assert_eq!(has_forbidden_strings(&"abweiriieiowery".to_string()), true);
-    assert_eq!(has_forbidden_strings(&"ababwwwwww".to_string()), true);
-    assert_eq!(has_forbidden_strings(&"".to_string()), false);
-    assert_eq!(has_forbidden_strings(&"cdabwwwwww".to_string()), true);
-    assert_eq!(has_forbidden_strings(&"adadxywwww".to_string()), true);
+    // one forbidden pair per string, next to strings with its letters apart
+    // or reversed
+    let forbidden = Forbidden(vec!["ab".into(), "cd".into(), "pq".into(), "xy".into()]);
+    let cases = [
+        ("", None),
+        ("abwwww", Some("ab")),
+        ("wwwwab", Some("ab")),
+        ("awbwww", None),
+        ("bawwww", None),
+        ("wcdwww", Some("cd")),
+        ("dcwwww", None),
+        ("wwpqww", Some("pq")),
+        ("qpwwww", None),
+        ("wwwxyw", Some("xy")),
+        ("yxwwww", None),
+        ("adwytgowhe", None),
+    ];
+    for (input, found) in cases {
+        let expected = found.map(|s| format!("contains the forbidden {:?}", s));
+        assert_eq!(forbidden.check(input).err(), expected, "{:?}", input);
+    }
+    // the first forbidden substring in the rule is reported
+    assert_eq!(
+        forbidden.check("xycd").err().as_deref(),
+        Some("contains the forbidden \"cd\"")
+    );
 
-    assert_eq!(atleast_three_vowels(&"adwytgowhe".to_string()), true);
-    assert_eq!(atleast_three_vowels(&"cdabwwwwww".to_string()), false);
-    assert_eq!(atleast_three_vowels(&"".to_string()), false);
-    assert_eq!(atleast_three_vowels(&"adadxywwwwa".to_string()), true);
+    let vowels = AtLeast {
+        count: 3,
+        of: "aeiou".chars().collect(),
+    };
+    assert!(vowels.check("adwytgowhe").is_ok());
+    assert!(vowels.check("cdabwwwwww").is_err());
+    assert!(vowels.check("").is_err());
+    assert!(vowels.check("adadxywwwwa").is_ok());
 
-    assert_eq!(atleast_one_double(&"abcdefgh".to_string()), false);
-    assert_eq!(atleast_one_double(&"".to_string()), false);
-    assert_eq!(atleast_one_double(&"z".to_string()), false);
-    assert_eq!(atleast_one_double(&"abccdef".to_string()), true);
+    let double = GapRepeat(0);
+    assert!(double.check("abcdefgh").is_err());
+    assert!(double.check("").is_err());
+    assert!(double.check("z").is_err());
+    assert!(double.check("abccdef").is_ok());
 
-    assert_eq!(is_nice_string(&"ugknbfddgicrmopn".to_string()), true);
-    assert_eq!(is_nice_string(&"aaa".to_string()), true);
-    assert_eq!(is_nice_string(&"jchzalrnumimnmhp".to_string()), false);
-    assert_eq!(is_nice_string(&"haegwjzuvuyypxyu".to_string()), false);
-    assert_eq!(is_nice_string(&"dvszwmarrgswjxmb".to_string()), false);
+    let part1 = Policy::parse(PART1_POLICY).unwrap();
+    assert!(part1.is_nice("ugknbfddgicrmopn"));
+    assert!(part1.is_nice("aaa"));
+    assert!(!part1.is_nice("jchzalrnumimnmhp"));
+    assert!(!part1.is_nice("haegwjzuvuyypxyu"));
+    assert!(!part1.is_nice("dvszwmarrgswjxmb"));
 
-    assert_eq!(double_pair_two(&"xyxy".to_string()), true);
-    assert_eq!(double_pair_two(&"aabcdefgaa".to_string()), true);
-    assert_eq!(double_pair_two(&"aaa".to_string()), false);
+    // (input, repeated with overlaps allowed, repeated without overlapping)
+    let pairs = [
+        ("", false, false),
+        ("a", false, false),
+        ("aa", false, false),
+        ("aaa", true, false),
+        ("aaaa", true, true),
+        ("aaaaa", true, true),
+        ("baaab", true, false),
+        ("baaaab", true, true),
+        ("aabaa", true, true),
+        ("aba", false, false),
+        ("abab", true, true),
+        ("xyxy", true, true),
+        ("aabcdefgaa", true, true),
+        ("abcdef", false, false),
+        ("ééé", true, false),
+    ];
+    let mut classifier = Classifier::new();
+    for (input, allowed, forbidden) in pairs {
+        let chars: Vec<char> = input.chars().collect();
+        assert_eq!(
+            has_repeated_pair(&chars, Overlap::Allowed),
+            allowed,
+            "{:?}",
+            input
+        );
+        assert_eq!(
+            has_repeated_pair(&chars, Overlap::Forbidden),
+            forbidden,
+            "{:?}",
+            input
+        );
+        assert_eq!(RepeatedPair(Overlap::Allowed).check(input).is_ok(), allowed);
+        assert_eq!(
+            RepeatedPair(Overlap::Forbidden).check(input).is_ok(),
+            forbidden
+        );
+        // with a gap repeat guaranteed, part 2 comes down to the pair rule
+        assert_eq!(classifier.classify(&format!("{}#xyx", input)).1, forbidden);
+    }
 
-    assert_eq!(three_long_palindrome(&"xyx".to_string()), true);
-    assert_eq!(three_long_palindrome(&"abcdefeghi".to_string()), true);
-    assert_eq!(three_long_palindrome(&"aaa".to_string()), true);
-    assert_eq!(three_long_palindrome(&"".to_string()), false);
-    assert_eq!(three_long_palindrome(&"aa".to_string()), false);
-    assert_eq!(three_long_palindrome(&"affa".to_string()), false);
-}
+    let palindrome = GapRepeat(1);
+    assert!(palindrome.check("xyx").is_ok());
+    assert!(palindrome.check("abcdefeghi").is_ok());
+    assert!(palindrome.check("aaa").is_ok());
+    assert!(palindrome.check("").is_err());
+    assert!(palindrome.check("aa").is_err());
+    assert!(palindrome.check("affa").is_err());
+
+    let part2 = Policy::parse(PART2_POLICY).unwrap();
+    assert!(part2.is_nice("qjhvhtzxzqqjkmpb"));
+    assert!(part2.is_nice("xxyxx"));
+    assert!(!part2.is_nice("uurcxstgmygtbstg"));
+    assert!(!part2.is_nice("ieodomkazucvgmuy"));
+
+    assert_eq!(
+        part1.explain("haegwjzuvuyypxyu"),
+        vec!["contains the forbidden \"xy\""]
+    );
+    assert_eq!(
+        part1.explain("dvszwmarrgswjxmb"),
+        vec!["contains 1 of \"aeiou\", needs at least 3"]
+    );
+    assert_eq!(
+        part2.explain("abc"),
+        vec![
+            "no pair of letters appears twice without overlapping",
+            "no letter repeats with 1 letter between",
+        ]
+    );
+    assert!(part2.explain("xxyxx").is_empty());
 
-fn is_nice_string(input: &String) -> bool {
-    !has_forbidden_strings(input) && atleast_three_vowels(input) && atleast_one_double(input)
+    let overlapping = Policy::parse("repeated-pair overlapping").unwrap();
+    assert!(overlapping.is_nice("aaa"));
+    assert_eq!(
+        overlapping.explain("abc"),
+        vec!["no pair of letters appears twice"]
+    );
+
+    let custom = Policy::parse("at-least 2 of xyz\n gap-repeat 2 ;").unwrap();
+    assert!(custom.is_nice("xabx"));
+    assert!(!custom.is_nice("xaby"));
+    assert!(Policy::parse("at-least two of abc").is_err());
+    assert!(Policy::parse("forbid").is_err());
+    assert!(Policy::parse("sparkle").is_err());
+
+    // the classifier agrees with the rule engine, on ASCII and beyond
+    let generated = generate_strings(2000, 12, 5);
+    let samples = [
+        "",
+        "a",
+        "aa",
+        "aaa",
+        "aaaa",
+        "xyxy",
+        "abcdefeghi",
+        "éé",
+        "aéaéa",
+        "aeé",
+    ];
+    for line in generated.lines().chain(samples) {
+        assert_eq!(
+            classifier.classify(line),
+            (part1.is_nice(line), part2.is_nice(line)),
+            "{:?}",
+            line
+        );
+    }
+    assert_eq!(classifier.classify("aéaéa"), (false, true));
+    assert_eq!(classifier.classify("aééioé"), (true, false));
+    assert_eq!(
+        count_nice("ugknbfddgicrmopn\naaa\nqjhvhtzxzqqjkmpb\n"),
+        (2, 1)
+    );
+
+    assert!(validate("ugknbfddgicrmopn\naaa\n").is_empty());
+    assert_eq!(
+        validate("aaa \nAbc"),
+        vec![
+            Violation::new(1, 4, "trailing whitespace"),
+            Violation::new(2, 1, "expected a lowercase letter"),
+        ]
+    );
 }
 
-fn is_new_nice_string(input: &String) -> bool {
-    double_pair_two(input) && three_long_palindrome(input)
+// A requirement on nice strings, which explains itself when it isn't met
+trait Rule {
+    fn check(&self, input: &str) -> Result<(), String>;
 }
 
-fn has_forbidden_strings(input: &String) -> bool {
-    let ab: Vec<_> = input.match_indices("ab").collect();
-    let cd: Vec<_> = input.match_indices("cd").collect();
-    let pq: Vec<_> = input.match_indices("pq").collect();
-    let xy: Vec<_> = input.match_indices("xy").collect();
+// contains at least `count` letters from `of`, counting repeats
+#[derive(Debug)]
+struct AtLeast {
+    count: usize,
+    of: Vec<char>,
+}
 
-    ab.len() + cd.len() + pq.len() + xy.len() != 0
+impl Rule for AtLeast {
+    fn check(&self, input: &str) -> Result<(), String> {
+        let found = input.chars().filter(|c| self.of.contains(c)).count();
+        if found >= self.count {
+            return Ok(());
+        }
+        Err(format!(
+            "contains {} of {:?}, needs at least {}",
+            found,
+            self.of.iter().collect::<String>(),
+            self.count
+        ))
+    }
 }
 
-fn atleast_three_vowels(input: &String) -> bool {
-    let a: Vec<_> = input.match_indices("a").collect();
-    let e: Vec<_> = input.match_indices("e").collect();
-    let i: Vec<_> = input.match_indices("i").collect();
-    let o: Vec<_> = input.match_indices("o").collect();
-    let u: Vec<_> = input.match_indices("u").collect();
+// contains none of the substrings
+#[derive(Debug)]
+struct Forbidden(Vec<String>);
 
-    a.len() + e.len() + i.len() + o.len() + u.len() >= 3
+impl Rule for Forbidden {
+    fn check(&self, input: &str) -> Result<(), String> {
+        match self.0.iter().find(|s| input.contains(s.as_str())) {
+            Some(s) => Err(format!("contains the forbidden {:?}", s)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Whether two occurrences of the same pair may share a letter. In `aaa` the
+// pair `aa` starts at 0 and at 1, which only counts as a repeat when overlaps
+// are allowed; `aaaa` has `aa` at 0 and 2 and is a repeat either way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Overlap {
+    Allowed,
+    Forbidden,
 }
 
-fn atleast_one_double(input: &String) -> bool {
-    for (i, c) in input.chars().enumerate() {
-        if i == 0 {
-            continue;
+impl Overlap {
+    // how far apart the starts of two occurrences of a pair must be
+    fn min_distance(self) -> usize {
+        match self {
+            Overlap::Allowed => 1,
+            Overlap::Forbidden => 2,
         }
+    }
+}
+
+// Whether some pair of adjacent items appears at least twice, with the two
+// occurrences starting at least `overlap.min_distance()` apart. Only the first
+// occurrence of every pair is kept, since it is the farthest from any later one.
+fn has_repeated_pair<T: Copy + Eq + Hash>(items: &[T], overlap: Overlap) -> bool {
+    let mut first = HashMap::new();
+    items.windows(2).enumerate().any(|(i, pair)| {
+        let start = *first.entry((pair[0], pair[1])).or_insert(i);
+        i - start >= overlap.min_distance()
+    })
+}
+
+// some pair of letters appears at least twice, overlapping or not
+#[derive(Debug)]
+struct RepeatedPair(Overlap);
 
-        if input
-            .chars()
-            .nth(i - 1)
-            .expect("unable to get previous character")
-            == c
-        {
-            return true;
+impl Rule for RepeatedPair {
+    fn check(&self, input: &str) -> Result<(), String> {
+        let chars: Vec<char> = input.chars().collect();
+        match (has_repeated_pair(&chars, self.0), self.0) {
+            (true, _) => Ok(()),
+            (false, Overlap::Allowed) => Err("no pair of letters appears twice".to_string()),
+            (false, Overlap::Forbidden) => {
+                Err("no pair of letters appears twice without overlapping".to_string())
+            }
         }
     }
-    false
 }
 
-fn double_pair_two(input: &String) -> bool {
-    for (i, c) in input.chars().enumerate() {
-        if i == 0 {
-            continue;
+// some letter appears again with exactly `gap` letters between, so a gap of
+// 0 is a double letter and a gap of 1 is a pattern like `xyx`
+#[derive(Debug)]
+struct GapRepeat(usize);
+
+impl Rule for GapRepeat {
+    fn check(&self, input: &str) -> Result<(), String> {
+        let chars: Vec<char> = input.chars().collect();
+        if chars.windows(self.0 + 2).any(|w| w[0] == w[self.0 + 1]) {
+            return Ok(());
+        }
+        match self.0 {
+            0 => Err("no letter appears twice in a row".to_string()),
+            1 => Err("no letter repeats with 1 letter between".to_string()),
+            gap => Err(format!("no letter repeats with {} letters between", gap)),
         }
+    }
+}
+
+// A string is nice under a policy when it meets all of its rules
+struct Policy {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Policy {
+    // Rules are separated by `;` or newlines:
+    //
+    //   at-least <N> of <letters>    e.g. `at-least 3 of aeiou`
+    //   forbid <substrings...>       e.g. `forbid ab cd pq xy`
+    //   repeated-pair [overlapping]  e.g. `repeated-pair` for `xyxy`, not `aaa`
+    //   gap-repeat <N>               e.g. `gap-repeat 1` for `xyx`
+    fn parse(policy: &str) -> Result<Policy, String> {
+        let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+
+        for rule in policy.split([';', '\n']).map(str::trim) {
+            let words: Vec<&str> = rule.split_whitespace().collect();
+            let number = |word: &str| {
+                word.parse()
+                    .map_err(|_| format!("Expected a number in {:?}, found {:?}", rule, word))
+            };
 
-        if input
-            .match_indices(&format!("{}{}", input.chars().nth(i - 1).unwrap(), c))
-            .collect::<Vec<_>>()
-            .len()
-            > 1
-        {
-            return true;
+            match words[..] {
+                [] => continue,
+                ["at-least", count, "of", letters] => rules.push(Box::new(AtLeast {
+                    count: number(count)?,
+                    of: letters.chars().collect(),
+                })),
+                ["forbid", ref substrings @ ..] if !substrings.is_empty() => rules.push(Box::new(
+                    Forbidden(substrings.iter().map(|s| s.to_string()).collect()),
+                )),
+                ["repeated-pair"] => rules.push(Box::new(RepeatedPair(Overlap::Forbidden))),
+                ["repeated-pair", "overlapping"] => {
+                    rules.push(Box::new(RepeatedPair(Overlap::Allowed)))
+                }
+                ["gap-repeat", gap] => rules.push(Box::new(GapRepeat(number(gap)?))),
+                _ => return Err(format!("Unknown rule: {:?}", rule)),
+            }
         }
+
+        Ok(Policy { rules })
+    }
+
+    fn is_nice(&self, input: &str) -> bool {
+        self.rules.iter().all(|rule| rule.check(input).is_ok())
+    }
+
+    // why `input` is naughty, one reason per rule it breaks
+    fn explain(&self, input: &str) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.check(input).err())
+            .collect()
     }
-    false
 }
 
-fn three_long_palindrome(input: &String) -> bool {
-    for (i, c) in input.chars().enumerate() {
-        if i == 0 || i == 1 {
-            continue;
+// Decides both puzzle policies in one pass over the bytes of a line.
+//
+// The rules are defined on Unicode scalar values, as in the rule engine. Lines
+// that are all ASCII (every valid input) are classified byte by byte, and any
+// other line is handed to the rule engine, which walks its chars.
+struct Classifier {
+    // for every ASCII pair, the generation of the line it was last seen in and
+    // where it first appeared in that line
+    pairs: Vec<(u32, u32)>,
+    generation: u32,
+    part1: Policy,
+    part2: Policy,
+}
+
+impl Classifier {
+    fn new() -> Classifier {
+        Classifier {
+            pairs: vec![(0, 0); 128 * 128],
+            generation: 0,
+            part1: Policy::parse(PART1_POLICY).unwrap(),
+            part2: Policy::parse(PART2_POLICY).unwrap(),
+        }
+    }
+
+    // whether `line` is nice under the part 1 and part 2 policies
+    fn classify(&mut self, line: &str) -> (bool, bool) {
+        if !line.is_ascii() {
+            return (self.part1.is_nice(line), self.part2.is_nice(line));
+        }
+
+        // a new generation invalidates the pairs of the previous lines without
+        // clearing the table, which only happens when the counter wraps
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.pairs.fill((0, 0));
+            self.generation = 1;
         }
 
-        if input.chars().nth(i - 2).unwrap() == c {
-            return true;
+        let bytes = line.as_bytes();
+        let mut vowels = 0;
+        let mut double = false;
+        let mut forbidden = false;
+        let mut repeated_pair = false;
+        let mut gap_repeat = false;
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            vowels += matches!(byte, b'a' | b'e' | b'i' | b'o' | b'u') as usize;
+            if i == 0 {
+                continue;
+            }
+
+            let prev = bytes[i - 1];
+            double |= prev == byte;
+            forbidden |= matches!(
+                (prev, byte),
+                (b'a', b'b') | (b'c', b'd') | (b'p', b'q') | (b'x', b'y')
+            );
+            gap_repeat |= i >= 2 && bytes[i - 2] == byte;
+
+            // pairs are numbered by the index of their first byte
+            let pair = &mut self.pairs[(prev as usize) << 7 | byte as usize];
+            let start = (i - 1) as u32;
+            if pair.0 != self.generation {
+                *pair = (self.generation, start);
+            } else if (start - pair.1) as usize >= Overlap::Forbidden.min_distance() {
+                repeated_pair = true;
+            }
         }
+
+        (
+            vowels >= 3 && double && !forbidden,
+            repeated_pair && gap_repeat,
+        )
     }
-    false
 }
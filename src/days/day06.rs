@@ -1,107 +1,771 @@
+use crate::etc::bench::{self, Rng};
+use crate::etc::image;
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use regex::Regex;
-use std::fs::read_to_string;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 
 ///////////////////////////////////////////////////////////////////////////////
 
+// the most lights the grids of single lights are used for, 4096x4096
+const DENSE_LIGHTS: usize = 1 << 24;
+
+// The state of every light under a model, in the cells picked by its state:
+// a bitset for on/off states, one state per light otherwise.
+#[derive(Clone, Debug, PartialEq)]
+struct LightGrid<M: LightModel> {
+    width: usize,
+    height: usize,
+    model: M,
+    cells: <M::State as Light>::Cells,
+}
+
+// How a light's state is stored by the compact grid
+trait Light: Copy + Default {
+    type Cells: Cells<Self>;
+}
+
+// The states of a whole grid, row-major
+trait Cells<S>: Clone + fmt::Debug + PartialEq {
+    fn new(width: usize, height: usize) -> Self;
+    // applies `light` to columns x1..=x2 of row y
+    fn update_row(&mut self, y: usize, x1: usize, x2: usize, light: impl Fn(&mut S));
+    fn total(&self, value: impl Fn(S) -> u64) -> u64;
+}
+
+// On/off states, with every row starting on a new word so a row is updated a
+// word at a time
+#[derive(Clone, Debug, PartialEq)]
+struct Bitset {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+// one state per light
+#[derive(Clone, Debug, PartialEq)]
+struct Flat<S> {
+    width: usize,
+    states: Vec<S>,
+}
+
+// The grid cut along every rectangle edge of a list of instructions, so each
+// cell is a region of lights that always change together. Its size depends on
+// the number of distinct edges instead of the number of lights, but it still
+// holds every cell: up to (2n + 1)² of them for n instructions, so it suits
+// huge grids with few instructions rather than many.
 #[derive(Clone, Debug, PartialEq)]
-struct DroneGrid {
+struct CompressedGrid<M: LightModel> {
+    // cell i spans columns xs[i]..xs[i + 1], likewise for rows
+    xs: Vec<usize>,
+    ys: Vec<usize>,
+    model: M,
+    states: Vec<M::State>,
+}
+
+// How a light reacts to each instruction and what it adds to the total. Part 1
+// and part 2 are two models of the same instructions, and every grid runs any
+// model.
+trait LightModel {
+    type State: Light;
+
+    fn turn_on(&self, state: &mut Self::State);
+    fn turn_off(&self, state: &mut Self::State);
+    fn toggle(&self, state: &mut Self::State);
+    fn set(&self, state: &mut Self::State, level: u32);
+    fn invert(&self, state: &mut Self::State);
+    fn value(&self, state: Self::State) -> u64;
+}
+
+// part 1: lights are on or off
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct OnOff;
+
+// part 2: lights have a brightness that can't go below zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Brightness;
+
+// A model of your own, where every instruction changes the brightness by one
+// operation, e.g. `on=+1 off=-1 toggle=*2` for toggles that double it.
+// Brightness never goes below zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Arithmetic {
+    on: Operation,
+    off: Operation,
+    toggle: Operation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operation {
+    Add(i64),
+    Multiply(u64),
+    Set(u64),
+}
+
+// The original grid of one struct per light, kept as the reference the
+// compact grid is benchmarked and tested against.
+#[derive(Clone, Debug, PartialEq)]
+struct DroneGrid<M: LightModel> {
     width: usize,
     height: usize,
-    grid: Vec<Vec<Drone>>,
+    model: M,
+    grid: Vec<Vec<Drone<M::State>>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct Drone {
+struct Drone<S> {
     x: usize,
     y: usize,
-    led_on: bool,
-    brightness: u64,
+    state: S,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 struct DroneCommand {
-    command: String,
+    action: Action,
     x1: usize,
     y1: usize,
     x2: usize,
     y2: usize,
 }
 
+// `set` and `invert` are extended instructions, only accepted with
+// `--extended`
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    TurnOn,
+    TurnOff,
+    Toggle,
+    Set(u32),
+    Invert,
+}
+
+// What the parser accepts: the width and height of the grid every rectangle
+// must fit in, if known before parsing, and whether the extended
+// instructions are allowed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Syntax {
+    size: Option<(usize, usize)>,
+    extended: bool,
+}
+
+// The words, numbers and punctuation of an instruction, with their 1-based
+// columns. Any run of spaces or tabs separates two tokens.
+struct Tokens<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+    end: usize,
+}
+
+// a parsed number and its column
+type Coordinate = (usize, usize);
+
+#[derive(Clone, Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day06.txt").expect("File not found");
+    let input = trace::in_span("parse", || config::read_input(6));
     driver(input)
 }
 
+pub fn validate(input: &str) -> Vec<Violation> {
+    let syntax = syntax();
+    // the parser takes any spaces and tabs around tokens, so only CRLF endings
+    // are whitespace problems
+    validate::check_raw_lines(input, |line| parse_drone_command(line, &syntax).map(|_| ()))
+}
+
 fn driver(input: String) -> SolutionPair {
-    let lines: Vec<String> = input.lines().map(str::to_string).collect();
-    let mut grid = DroneGrid::new(1000, 1000);
-    for line in lines {
-        match parse_drone_command(line.as_str()) {
-            Ok(dc) => match dc.command.as_str() {
-                "turn on" => {
-                    grid.turn_on_rect(dc.x1, dc.y1, dc.x2, dc.y2);
-                }
-                "turn off" => {
-                    grid.turn_off_rect(dc.x1, dc.y1, dc.x2, dc.y2);
-                }
-                "toggle" => {
-                    grid.toggle_rect(dc.x1, dc.y1, dc.x2, dc.y2);
-                }
-                _ => {
-                    panic!("Unknown command: {}", dc.command);
-                }
-            },
-            Err(_) => {
-                println!("Error parsing command: {}", line);
+    let syntax = syntax();
+    let commands = parse_instructions(&input, &syntax).unwrap_or_else(|e| panic!("{}", e));
+    let (width, height) = syntax.size.unwrap_or_else(|| fit(&commands));
+    if width.checked_mul(height).is_none() {
+        panic!("A {}x{} grid has too many lights to count", width, height);
+    }
+
+    // `--solver compressed` works on the regions between rectangle edges
+    // instead of single lights, `--solver reference` uses the original grid.
+    // Grids too large for single lights are compressed by default.
+    let solver = if dense(width, height) {
+        "grid"
+    } else {
+        "compressed"
+    };
+    let solver = config::day_option(6, "solver").unwrap_or(solver);
+    let sol1 = trace::in_span("part1", || {
+        run_model(&commands, solver, width, height, OnOff)
+    });
+    let sol2 = trace::in_span("part2", || {
+        run_model(&commands, solver, width, height, Brightness)
+    });
+
+    // `--model "on=+1 off=-1 toggle=*2"` also totals the lights under a model
+    // of your own, on the same grid
+    if let Some(model) = config::day_option(6, "model") {
+        let arithmetic: Arithmetic = model.parse().unwrap_or_else(|e| panic!("{}", e));
+        let total = trace::in_span("model", || {
+            run_model(&commands, solver, width, height, arithmetic)
+        });
+        println!("Total under {:?}: {}", model, total);
+    }
+
+    // `--render lights.png` (or `.pgm`) draws the brightness of the lights at
+    // the end and `--frames dir/` one PGM frame every `--frame-every` instructions (default 1)
+    let render = config::day_option(6, "render");
+    let frames = config::day_option(6, "frames");
+    if render.is_some() || frames.is_some() {
+        let every = config::day_option(6, "frame-every").map_or(1, |x| {
+            x.parse()
+                .expect("Expected a number of instructions per frame")
+        });
+        trace::in_span("render", || {
+            export(&commands, (width, height), render, frames, every)
+        });
+    }
+
+    (Solution::from(sol1), Solution::from(sol2))
+}
+
+// `--size 2000x500` sets the width and height of the grid, 1000x1000 by
+// default, and `--size auto` fits it to the instructions. `--extended` also
+// accepts `set <n>` and `invert` instructions.
+fn syntax() -> Syntax {
+    let size = match config::day_option(6, "size").unwrap_or("1000x1000") {
+        "auto" => None,
+        size => Some(parse_size(size).unwrap_or_else(|| {
+            panic!("Expected a size such as 1000x1000 or auto, found {}", size)
+        })),
+    };
+    Syntax {
+        size,
+        extended: config::day_option(6, "extended").is_some_and(|x| x != "false"),
+    }
+}
+
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+// the smallest grid holding every rectangle
+fn fit(commands: &[DroneCommand]) -> (usize, usize) {
+    let width = commands.iter().map(|c| c.x2 + 1).max().unwrap_or(1);
+    let height = commands.iter().map(|c| c.y2 + 1).max().unwrap_or(1);
+    (width, height)
+}
+
+// whether the grids of single lights can hold a grid this size
+fn dense(width: usize, height: usize) -> bool {
+    width
+        .checked_mul(height)
+        .is_some_and(|lights| lights <= DENSE_LIGHTS)
+}
+
+fn check_dense(width: usize, height: usize, solver: &str) {
+    if !dense(width, height) {
+        panic!(
+            "A {}x{} grid is too large for {} (try --solver compressed)",
+            width, height, solver
+        );
+    }
+}
+
+// Draws the lights of the compact grid as grayscale images, the brightest
+// light in white. All frames share the brightest light of the whole sequence,
+// so a light keeps its shade from one frame to the next.
+fn export(
+    commands: &[DroneCommand],
+    (width, height): (usize, usize),
+    render: Option<&str>,
+    frames: Option<&str>,
+    every: usize,
+) {
+    assert!(every > 0, "Expected at least one instruction per frame");
+    if let Some(path) = render.filter(|path| !path.ends_with(".pgm") && !path.ends_with(".png")) {
+        panic!("Expected a .png or .pgm path to render to, found {}", path);
+    }
+
+    // a frame after every `every` instructions, and after the last one
+    let is_frame = |i: usize| (i + 1).is_multiple_of(every) || i + 1 == commands.len();
+    check_dense(width, height, "rendering");
+    let new_grid = || LightGrid::new(width, height, Brightness);
+
+    let mut grid = new_grid();
+    let mut peak = 0;
+    for (i, command) in commands.iter().enumerate() {
+        command.apply_to(&mut grid);
+        if is_frame(i) {
+            peak = peak.max(grid.peak());
+        }
+    }
+
+    if let Some(path) = render {
+        write_image(&grid, Path::new(path), grid.peak());
+        println!("Lights written to {} ({}x{})", path, width, height);
+    }
+
+    if let Some(dir) = frames {
+        fs::create_dir_all(dir).unwrap_or_else(|e| panic!("Could not create {}: {}", dir, e));
+        let digits = commands.len().div_ceil(every).to_string().len();
+        let mut grid = new_grid();
+        let mut frame = 0;
+        for (i, command) in commands.iter().enumerate() {
+            command.apply_to(&mut grid);
+            if is_frame(i) {
+                frame += 1;
+                let path = Path::new(dir).join(format!("frame{:0digits$}.pgm", frame));
+                write_image(&grid, &path, peak);
             }
         }
+        println!("{} frames written to {}", frame, dir);
     }
-    let sol1 = grid.count_lit();
-    let sol2: u64 = grid.brightness();
-    (Solution::from(sol1), Solution::from(sol2))
+}
+
+// a PNG for `.png` paths, a PGM otherwise
+fn write_image(grid: &LightGrid<Brightness>, path: &Path, peak: u64) {
+    let (maxval, pixels) = grid.image(peak);
+    let write = if path.extension().is_some_and(|extension| extension == "png") {
+        image::write_png
+    } else {
+        image::write_pgm
+    };
+    write(path, grid.width, grid.height, maxval, &pixels)
+        .unwrap_or_else(|e| panic!("Could not write {}: {}", path.display(), e));
+}
+
+// the total of the lights under `model`, on the grid named by `solver`
+fn run_model<M: LightModel>(
+    commands: &[DroneCommand],
+    solver: &str,
+    width: usize,
+    height: usize,
+    model: M,
+) -> u64 {
+    match solver {
+        "grid" => {
+            check_dense(width, height, "--solver grid");
+            run_commands(commands, || LightGrid::new(width, height, model))
+        }
+        "compressed" => run_commands(commands, || {
+            CompressedGrid::new(width, height, commands, model)
+        }),
+        "reference" => {
+            check_dense(width, height, "--solver reference");
+            run_commands(commands, || DroneGrid::new(width, height, model))
+        }
+        solver => panic!(
+            "Unknown solver: {} (expected grid, compressed or reference)",
+            solver
+        ),
+    }
+}
+
+fn run_commands<L: Lights>(commands: &[DroneCommand], new_grid: impl FnOnce() -> L) -> u64 {
+    let mut grid = trace::in_span("new_grid", new_grid);
+    for command in commands {
+        command.apply_to(&mut grid);
+    }
+    grid.total()
+}
+
+// Compares the compact and compressed grids with the reference one on random
+// instructions.
+// `--bench-instructions` sets how many (default 300).
+pub fn bench() {
+    let count: usize = config::day_option(6, "bench-instructions").map_or(300, |x| {
+        x.parse().expect("Expected a number of instructions")
+    });
+    let commands = generate_commands(count, 1000, 2015);
+
+    println!("\n=== Day 06 · {} instructions ===", count);
+    let both = |solver| {
+        (
+            run_model(&commands, solver, 1000, 1000, OnOff),
+            run_model(&commands, solver, 1000, 1000, Brightness),
+        )
+    };
+    let expected = bench::measure("DroneGrid", 0, || both("reference"));
+    let compact = bench::measure("LightGrid", 0, || both("grid"));
+    let compressed = bench::measure("CompressedGrid", 0, || both("compressed"));
+
+    assert_eq!(expected, compact);
+    assert_eq!(expected, compressed);
+}
+
+// random instructions over a `size` x `size` grid
+fn generate_commands(count: usize, size: u64, seed: u64) -> Vec<DroneCommand> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|_| {
+            let action = [Action::TurnOn, Action::TurnOff, Action::Toggle][rng.below(3) as usize];
+            let (x1, x2) = (rng.below(size), rng.below(size));
+            let (y1, y2) = (rng.below(size), rng.below(size));
+            DroneCommand {
+                action,
+                x1: x1.min(x2) as usize,
+                y1: y1.min(y2) as usize,
+                x2: x1.max(x2) as usize,
+                y2: y1.max(y2) as usize,
+            }
+        })
+        .collect()
 }
 
 #[test]
 fn test() {
+    use std::fs::read_to_string;
+
     let input = "turn on 0,0 through 999,999";
     let bad_input = "fail this 0, 0 and 999, 999";
 
     // test the grid and rectangle structs
-    let mut grid = DroneGrid::new(1000, 1000);
+    let mut grid = LightGrid::new(1000, 1000, OnOff);
     grid.turn_on_rect(0, 0, 999, 999);
-    assert_eq!(grid.count_lit(), 1_000_000);
+    assert_eq!(grid.total(), 1_000_000);
 
-    let mut grid = DroneGrid::new(1000, 1000);
+    let mut grid = LightGrid::new(1000, 1000, OnOff);
     grid.toggle_rect(0, 0, 999, 0);
-    assert_eq!(grid.count_lit(), 1000);
+    assert_eq!(grid.total(), 1000);
 
-    let mut grid = DroneGrid::new(1000, 1000);
+    let mut grid = LightGrid::new(1000, 1000, OnOff);
     grid.turn_on_rect(0, 0, 999, 999);
     grid.turn_off_rect(499, 499, 500, 500);
-    assert_eq!(grid.count_lit(), 999_996);
+    assert_eq!(grid.total(), 999_996);
 
-    // test the parser
-    match parse_drone_command(input) {
-        Ok(dc) => {
-            assert_eq!(dc.command, "turn on");
-            assert_eq!(dc.x1, 0);
-            assert_eq!(dc.y1, 0);
-            assert_eq!(dc.x2, 999);
-            assert_eq!(dc.y2, 999);
-        }
-        Err(_) => {
-            panic!("Error parsing drone command: {}", input);
-        }
+    // rectangles inside a word, across word boundaries and on the last
+    // partial word of each row
+    let mut grid = LightGrid::new(130, 3, OnOff);
+    let mut brightness = LightGrid::new(130, 3, Brightness);
+    grid.turn_on_rect(3, 0, 5, 0);
+    grid.toggle_rect(60, 1, 70, 2);
+    grid.toggle_rect(0, 2, 129, 2);
+    brightness.turn_on_rect(3, 0, 5, 0);
+    brightness.toggle_rect(60, 1, 70, 2);
+    brightness.toggle_rect(0, 2, 129, 2);
+    assert_eq!(grid.total(), 3 + 11 + 130 - 11);
+    assert_eq!(brightness.total(), 3 + 2 * 11 + 2 * 11 + 2 * 130);
+    assert!(grid.is_lit(129, 2) && !grid.is_lit(65, 2) && grid.is_lit(65, 1));
+    grid.turn_off_rect(0, 0, 129, 2);
+    brightness.turn_off_rect(0, 0, 129, 2);
+    assert_eq!(grid.total(), 0);
+    assert_eq!(
+        brightness.total(),
+        3 + 2 * 11 + 2 * 11 + 2 * 130 - 3 - 11 - 130
+    );
+
+    // the compact and compressed grids agree with the reference one, under
+    // every model
+    let arithmetic: Arithmetic = "on=+3 off=-1 toggle=*2".parse().unwrap();
+    for seed in 1..=5 {
+        let commands = generate_commands(50, 150, seed);
+        let totals = |solver| {
+            (
+                run_model(&commands, solver, 150, 150, OnOff),
+                run_model(&commands, solver, 150, 150, Brightness),
+                run_model(&commands, solver, 150, 150, arithmetic),
+            )
+        };
+        let expected = totals("reference");
+        assert_eq!(totals("grid"), expected);
+        assert_eq!(totals("compressed"), expected);
     }
 
-    match parse_drone_command(bad_input) {
-        Ok(_) => {
-            panic!("Should have failed to parse: {}", bad_input);
+    // one region per distinct edge, whatever the size of the grid
+    let commands = generate_commands(3, 1000, 7);
+    let grid = CompressedGrid::new(1000, 1000, &commands, OnOff);
+    assert!(grid.states.len() <= 7 * 7);
+
+    // the puzzle's models are arithmetic models too
+    let commands = generate_commands(30, 1000, 8);
+    let arithmetic = |model: &str| {
+        run_model(
+            &commands,
+            "compressed",
+            1000,
+            1000,
+            model.parse::<Arithmetic>().unwrap(),
+        )
+    };
+    assert_eq!(
+        arithmetic("on=+1 off=-1 toggle=+2"),
+        run_model(&commands, "compressed", 1000, 1000, Brightness)
+    );
+    assert_eq!(
+        "on==1, off==0".parse::<Arithmetic>().unwrap(),
+        Arithmetic {
+            on: Operation::Set(1),
+            off: Operation::Set(0),
+            toggle: Operation::Add(0),
         }
-        Err(_) => {}
+    );
+    assert!("on=+1 blink=*2".parse::<Arithmetic>().is_err());
+    assert!("on=/2".parse::<Arithmetic>().is_err());
+    for model in [
+        "toggle=*-2",
+        "on==-1",
+        "on=--5",
+        "on=++5",
+        "on=+",
+        "on=+99999999999999999999",
+    ] {
+        assert_eq!(
+            model.parse::<Arithmetic>(),
+            Err(format!("Expected a number in {:?}", model))
+        );
+    }
+
+    // toggles that double: 1 after turning on, then 2, then 4, then 3 after
+    // turning off, on the 4 lights of the rectangle
+    let syntax = Syntax {
+        size: Some((1000, 1000)),
+        extended: false,
+    };
+    let commands = parse_instructions(
+        "turn on 0,0 through 1,1\n\
+         toggle 0,0 through 1,1\n\
+         toggle 0,0 through 1,1\n\
+         turn off 0,0 through 1,1",
+        &syntax,
+    )
+    .unwrap();
+    for solver in ["grid", "compressed", "reference"] {
+        let total = |model: &str| {
+            run_model(
+                &commands,
+                solver,
+                1000,
+                1000,
+                model.parse::<Arithmetic>().unwrap(),
+            )
+        };
+        assert_eq!(total("on=+1 off=-1 toggle=*2"), 4 * 3);
+        assert_eq!(total("on=+5 off=-50 toggle=*3"), 0);
+        assert_eq!(total("on==18446744073709551615"), u64::MAX);
+        assert_eq!(total("off==18446744073709551615"), u64::MAX);
     }
 
+    // test the parser
+    assert_eq!(
+        parse_drone_command(input, &syntax),
+        Ok(DroneCommand {
+            action: Action::TurnOn,
+            x1: 0,
+            y1: 0,
+            x2: 999,
+            y2: 999,
+        })
+    );
+    assert_eq!(
+        parse_drone_command("  turn   on\t0 , 0   through 999,999  ", &syntax),
+        parse_drone_command(input, &syntax)
+    );
+
+    let extended = Syntax {
+        extended: true,
+        ..syntax
+    };
+    for (line, syntax, column, message) in [
+        (
+            bad_input,
+            &syntax,
+            1,
+            "expected `turn` or `toggle`, found \"fail\"",
+        ),
+        (
+            "turn up 0,0 through 1,1",
+            &syntax,
+            6,
+            "expected `on` or `off`, found \"up\"",
+        ),
+        (
+            "toggle 0,0 to 1,1",
+            &syntax,
+            12,
+            "expected `through`, found \"to\"",
+        ),
+        (
+            "toggle 0,0 through 1",
+            &syntax,
+            21,
+            "expected `,`, found end of line",
+        ),
+        (
+            "toggle 0,0 through 1,1 twice",
+            &syntax,
+            24,
+            "expected end of line, found \"twice\"",
+        ),
+        (
+            "toggle -1,0 through 1,1",
+            &syntax,
+            8,
+            "expected a coordinate, found \"-\"",
+        ),
+        (
+            "toggle 5,0 through 3,1",
+            &syntax,
+            20,
+            "x2 (3) is less than x1 (5)",
+        ),
+        (
+            "toggle 0,0 through 999,1000",
+            &syntax,
+            24,
+            "y2 (1000) is outside the grid (0 to 999)",
+        ),
+        (
+            "toggle 0,0 through 99999999999999999999999,1",
+            &syntax,
+            20,
+            "99999999999999999999999 is too large",
+        ),
+        (
+            "set 3 0,0 through 1,1",
+            &syntax,
+            1,
+            "`set` is an extended instruction (see --extended)",
+        ),
+        (
+            "set bright 0,0 through 1,1",
+            &extended,
+            5,
+            "expected a brightness, found \"bright\"",
+        ),
+        (
+            "blink 0,0 through 1,1",
+            &extended,
+            1,
+            "expected `turn`, `toggle`, `set` or `invert`, found \"blink\"",
+        ),
+    ] {
+        assert_eq!(
+            parse_drone_command(line, syntax),
+            Err((column, message.to_string())),
+            "{}",
+            line
+        );
+    }
+    assert_eq!(
+        parse_instructions("toggle 0,0 through 1,1\nturn of 0,0 through 1,1", &syntax),
+        Err(ParseError {
+            line: 2,
+            column: 6,
+            message: "expected `on` or `off`, found \"of\"".to_string(),
+        })
+    );
+
+    assert!(validate(input).is_empty());
+    assert_eq!(validate(bad_input).len(), 1);
+    assert!(validate("  turn   on\t0 , 0   through 999,999  \n").is_empty());
+    assert_eq!(
+        validate("toggle 0,0 through 1,1\n\nturn on 0,0 through 1,1\r\n"),
+        vec![
+            Violation::new(2, 1, "expected `turn` or `toggle`, found end of line"),
+            Violation::new(3, 24, "CRLF line ending"),
+        ]
+    );
+
+    // the extended instructions behave the same on every grid: 4 lights at
+    // 1 and 4 at 5, then a row inverted and one light switched off
+    let commands = parse_instructions(
+        "turn on 0,0 through 1,1\n\
+         set 5 1,1 through 2,2\n\
+         invert 0,0 through 2,0\n\
+         set 0 2,2 through 2,2",
+        &extended,
+    )
+    .unwrap();
+    let brightest = parse_instructions(
+        "set 4294967295 0,0 through 0,0\n\
+         turn on 0,0 through 0,0\n\
+         toggle 0,0 through 0,0",
+        &extended,
+    )
+    .unwrap();
+    for solver in ["grid", "compressed", "reference"] {
+        assert_eq!(
+            run_model(&brightest, solver, 1, 1, Brightness),
+            u32::MAX as u64
+        );
+        assert_eq!(run_model(&commands, solver, 3, 3, OnOff), 5);
+        assert_eq!(
+            run_model(&commands, solver, 3, 3, Brightness),
+            1 + 5 * 3 + 1
+        );
+    }
+
+    // grids of any size: non-square ones, fitted ones and sparse ones far
+    // too large for single lights
+    let wide = Syntax {
+        size: Some((2000, 500)),
+        extended: false,
+    };
+    assert!(parse_drone_command("toggle 1500,0 through 1999,499", &wide).is_ok());
+    assert_eq!(
+        parse_drone_command("toggle 0,0 through 499,500", &wide),
+        Err((24, "y2 (500) is outside the grid (0 to 499)".to_string()))
+    );
+    assert_eq!(parse_size("2000x500"), Some((2000, 500)));
+    assert_eq!(parse_size("0x500"), None);
+    assert_eq!(parse_size("2000"), None);
+
+    let commands = parse_instructions(
+        "turn on 0,0 through 999999,999999\n\
+         toggle 0,0 through 499999,0\n\
+         turn off 5,5 through 1999999,5",
+        &Syntax {
+            size: None,
+            extended: false,
+        },
+    )
+    .unwrap();
+    let (width, height) = fit(&commands);
+    assert_eq!((width, height), (2_000_000, 1_000_000));
+    let auto = Syntax {
+        size: None,
+        extended: false,
+    };
+    let max = usize::MAX.to_string();
+    assert_eq!(
+        parse_drone_command(&format!("toggle 0,0 through 5,{}", max), &auto),
+        Err((22, format!("{} is too large", max)))
+    );
+    assert!(
+        parse_drone_command(&format!("toggle 0,0 through 5,{}", usize::MAX - 1), &auto).is_ok()
+    );
+    assert!(!dense(width, height) && dense(4096, 4096));
+    assert_eq!(
+        run_model(&commands, "compressed", width, height, OnOff),
+        1_000_000_000_000 - 500_000 - 999_995
+    );
+    assert_eq!(
+        run_model(&commands, "compressed", width, height, Brightness),
+        1_000_000_000_000 + 2 * 500_000 - 999_995
+    );
+
+    // images of the final brightness, scaled to the brightest light
+    let mut grid = LightGrid::new(3, 2, Brightness);
+    grid.toggle_rect(0, 0, 1, 0);
+    grid.turn_on_rect(1, 0, 2, 1);
+    assert_eq!(grid.peak(), 3);
+    assert_eq!(grid.image(3), (3, vec![2, 3, 1, 0, 1, 1]));
+    assert_eq!(grid.image(6), (6, vec![2, 3, 1, 0, 1, 1]));
+    assert_eq!(grid.image(2), (2, vec![2, 2, 1, 0, 1, 1]));
+    assert_eq!(
+        grid.image(300_000).1,
+        vec![0, 0, 0, 0, 0, 0],
+        "a peak beyond 16 bits scales down"
+    );
+    assert_eq!(LightGrid::new(2, 1, Brightness).image(0), (1, vec![0, 0]));
+
     // integration tests
     let input = read_to_string("input/test/day06.txt").unwrap();
     let (sol1, sol2) = driver(input);
@@ -114,40 +778,460 @@ fn test() {
     assert_eq!(sol2, Solution::U64(2_000_001));
 }
 
-impl Drone {
-    fn turn_on(self: &mut Drone) -> &mut Drone {
-        self.led_on = true;
-        self.brightness += 1;
-        self
+impl DroneCommand {
+    fn apply_to(&self, grid: &mut impl Lights) {
+        let (x1, y1, x2, y2) = (self.x1, self.y1, self.x2, self.y2);
+        match self.action {
+            Action::TurnOn => grid.turn_on_rect(x1, y1, x2, y2),
+            Action::TurnOff => grid.turn_off_rect(x1, y1, x2, y2),
+            Action::Toggle => grid.toggle_rect(x1, y1, x2, y2),
+            Action::Set(level) => grid.set_rect(x1, y1, x2, y2, level),
+            Action::Invert => grid.invert_rect(x1, y1, x2, y2),
+        }
+    }
+}
+
+type State<L> = <<L as Lights>::Model as LightModel>::State;
+
+// What all the grids can do, so the same commands drive any of them: apply the
+// model to every light of a rectangle, and total the lights under it. Totals
+// saturate at u64::MAX, as the lights of an arithmetic model do.
+trait Lights {
+    type Model: LightModel;
+
+    fn update_rect(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        light: impl Fn(&Self::Model, &mut State<Self>),
+    );
+    fn total(&self) -> u64;
+
+    fn turn_on_rect(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        let _span = trace::span("turn_on_rect");
+        self.update_rect(x1, y1, x2, y2, |model, state| model.turn_on(state));
+    }
+
+    fn turn_off_rect(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        let _span = trace::span("turn_off_rect");
+        self.update_rect(x1, y1, x2, y2, |model, state| model.turn_off(state));
+    }
+
+    fn toggle_rect(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        let _span = trace::span("toggle_rect");
+        self.update_rect(x1, y1, x2, y2, |model, state| model.toggle(state));
+    }
+
+    fn set_rect(&mut self, x1: usize, y1: usize, x2: usize, y2: usize, level: u32) {
+        let _span = trace::span("set_rect");
+        self.update_rect(x1, y1, x2, y2, |model, state| model.set(state, level));
+    }
+
+    fn invert_rect(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        let _span = trace::span("invert_rect");
+        self.update_rect(x1, y1, x2, y2, |model, state| model.invert(state));
+    }
+}
+
+impl<M: LightModel> Lights for LightGrid<M> {
+    type Model = M;
+
+    fn update_rect(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        light: impl Fn(&M, &mut M::State),
+    ) {
+        for y in y1..=y2 {
+            self.cells
+                .update_row(y, x1, x2, |state| light(&self.model, state));
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.cells.total(|state| self.model.value(state))
+    }
+}
+
+impl<M: LightModel> Lights for DroneGrid<M> {
+    type Model = M;
+
+    fn update_rect(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        light: impl Fn(&M, &mut M::State),
+    ) {
+        for y in y1..=y2 {
+            for x in x1..=x2 {
+                light(&self.model, &mut self.grid[y][x].state);
+            }
+        }
+    }
+
+    fn total(&self) -> u64 {
+        let mut total: u64 = 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                total = total.saturating_add(self.model.value(self.grid[y][x].state));
+            }
+        }
+        total
+    }
+}
+
+impl<M: LightModel> Lights for CompressedGrid<M> {
+    type Model = M;
+
+    fn update_rect(
+        &mut self,
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        light: impl Fn(&M, &mut M::State),
+    ) {
+        let columns = self.xs.len() - 1;
+        let (cx1, cx2) = Self::span(&self.xs, x1, x2);
+        let (cy1, cy2) = Self::span(&self.ys, y1, y2);
+        for cy in cy1..=cy2 {
+            for cell in cy * columns + cx1..=cy * columns + cx2 {
+                light(&self.model, &mut self.states[cell]);
+            }
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.cells()
+            .map(|(cell, area)| self.model.value(self.states[cell]).saturating_mul(area))
+            .fold(0, u64::saturating_add)
+    }
+}
+
+impl Light for bool {
+    type Cells = Bitset;
+}
+
+impl Light for u32 {
+    type Cells = Flat<u32>;
+}
+
+impl Light for u64 {
+    type Cells = Flat<u64>;
+}
+
+impl Cells<bool> for Bitset {
+    fn new(width: usize, height: usize) -> Bitset {
+        let words_per_row = width.div_ceil(64);
+        Bitset {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
     }
 
-    fn turn_off(self: &mut Drone) -> &mut Drone {
-        self.led_on = false;
-        if self.brightness > 0 {
-            self.brightness -= 1;
+    // Any change of an on/off state keeps it, flips it or sets it, which is
+    // worked out once from what it does to a light that's on and one that's
+    // off, then applied to whole words through the mask of the columns.
+    fn update_row(&mut self, y: usize, x1: usize, x2: usize, light: impl Fn(&mut bool)) {
+        let after = |mut state| {
+            light(&mut state);
+            state
+        };
+        let (from_on, from_off) = (after(true), after(false));
+
+        let row = &mut self.words[y * self.words_per_row..(y + 1) * self.words_per_row];
+        let (first_word, last_word) = (x1 / 64, x2 / 64);
+        for (w, word) in row
+            .iter_mut()
+            .enumerate()
+            .take(last_word + 1)
+            .skip(first_word)
+        {
+            let first = if w == first_word { x1 % 64 } else { 0 };
+            let last = if w == last_word { x2 % 64 } else { 63 };
+            let mask = (u64::MAX >> (63 - last)) & (u64::MAX << first);
+            let on = if from_on { *word } else { 0 };
+            let off = if from_off { !*word } else { 0 };
+            *word = (*word & !mask) | ((on | off) & mask);
         }
-        self
     }
 
-    fn toggle(self: &mut Drone) -> &mut Drone {
-        self.led_on = !self.led_on;
-        self.brightness += 2;
-        self
+    fn total(&self, value: impl Fn(bool) -> u64) -> u64 {
+        let lit: u64 = self.words.iter().map(|word| word.count_ones() as u64).sum();
+        let dark = (self.width * self.height) as u64 - lit;
+        lit.saturating_mul(value(true))
+            .saturating_add(dark.saturating_mul(value(false)))
+    }
+}
+
+impl<S: Light + fmt::Debug + PartialEq> Cells<S> for Flat<S> {
+    fn new(width: usize, height: usize) -> Flat<S> {
+        Flat {
+            width,
+            states: vec![S::default(); width * height],
+        }
+    }
+
+    fn update_row(&mut self, y: usize, x1: usize, x2: usize, light: impl Fn(&mut S)) {
+        let start = y * self.width;
+        self.states[start + x1..=start + x2]
+            .iter_mut()
+            .for_each(light);
+    }
+
+    fn total(&self, value: impl Fn(S) -> u64) -> u64 {
+        self.states
+            .iter()
+            .map(|&state| value(state))
+            .fold(0, u64::saturating_add)
+    }
+}
+
+impl LightModel for OnOff {
+    type State = bool;
+
+    fn turn_on(&self, state: &mut bool) {
+        *state = true;
+    }
+
+    fn turn_off(&self, state: &mut bool) {
+        *state = false;
+    }
+
+    fn toggle(&self, state: &mut bool) {
+        *state = !*state;
+    }
+
+    fn set(&self, state: &mut bool, level: u32) {
+        *state = level > 0;
+    }
+
+    fn invert(&self, state: &mut bool) {
+        *state = !*state;
+    }
+
+    fn value(&self, state: bool) -> u64 {
+        state as u64
+    }
+}
+
+impl LightModel for Brightness {
+    type State = u32;
+
+    // `set` can reach u32::MAX, which stays the brightest a light gets
+    fn turn_on(&self, state: &mut u32) {
+        *state = state.saturating_add(1);
+    }
+
+    fn turn_off(&self, state: &mut u32) {
+        *state = state.saturating_sub(1);
+    }
+
+    fn toggle(&self, state: &mut u32) {
+        *state = state.saturating_add(2);
+    }
+
+    fn set(&self, state: &mut u32, level: u32) {
+        *state = level;
+    }
+
+    // dark lights light up at 1, all others go dark
+    fn invert(&self, state: &mut u32) {
+        *state = (*state == 0) as u32;
+    }
+
+    fn value(&self, state: u32) -> u64 {
+        state as u64
+    }
+}
+
+impl LightModel for Arithmetic {
+    type State = u64;
+
+    fn turn_on(&self, state: &mut u64) {
+        self.on.apply(state);
+    }
+
+    fn turn_off(&self, state: &mut u64) {
+        self.off.apply(state);
+    }
+
+    fn toggle(&self, state: &mut u64) {
+        self.toggle.apply(state);
+    }
+
+    fn set(&self, state: &mut u64, level: u32) {
+        *state = level as u64;
+    }
+
+    fn invert(&self, state: &mut u64) {
+        *state = (*state == 0) as u64;
+    }
+
+    fn value(&self, state: u64) -> u64 {
+        state
     }
 }
 
-impl DroneGrid {
-    fn new(width: usize, height: usize) -> DroneGrid {
+impl Operation {
+    fn apply(self, state: &mut u64) {
+        *state = match self {
+            Operation::Add(n) => state.saturating_add_signed(n),
+            Operation::Multiply(n) => state.saturating_mul(n),
+            Operation::Set(n) => n,
+        };
+    }
+}
+
+// `on=+1 off=-1 toggle=*2`, separated by spaces or commas. Operations are
+// `+n`, `-n`, `*n` and `=n`, and instructions left out don't change a light.
+impl FromStr for Arithmetic {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Arithmetic, String> {
+        let mut model = Arithmetic {
+            on: Operation::Add(0),
+            off: Operation::Add(0),
+            toggle: Operation::Add(0),
+        };
+
+        for part in string.split([' ', ',']).filter(|part| !part.is_empty()) {
+            let (instruction, operation) = part
+                .split_once('=')
+                .ok_or(format!("Expected instruction=operation, found {:?}", part))?;
+            // digits only, so signs after the operation like `*-2` or `--5`
+            // are rejected rather than wrapped or doubled
+            let error = || format!("Expected a number in {:?}", part);
+            let number = |n: &str| -> Result<u64, String> {
+                if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(error());
+                }
+                n.parse().map_err(|_| error())
+            };
+            let amount = |n: &str| i64::try_from(number(n)?).map_err(|_| error());
+            let operation = match operation.split_at_checked(1) {
+                Some(("+", n)) => Operation::Add(amount(n)?),
+                Some(("-", n)) => Operation::Add(-amount(n)?),
+                Some(("*", n)) => Operation::Multiply(number(n)?),
+                Some(("=", n)) => Operation::Set(number(n)?),
+                _ => return Err(format!("Unknown operation in {:?}", part)),
+            };
+            match instruction {
+                "on" => model.on = operation,
+                "off" => model.off = operation,
+                "toggle" => model.toggle = operation,
+                _ => return Err(format!("Unknown instruction in {:?}", part)),
+            }
+        }
+
+        Ok(model)
+    }
+}
+
+impl<M: LightModel> CompressedGrid<M> {
+    fn new(width: usize, height: usize, commands: &[DroneCommand], model: M) -> CompressedGrid<M> {
+        let edges = |size: usize, rects: &mut dyn Iterator<Item = (usize, usize)>| {
+            let mut edges = vec![0, size];
+            for (from, to) in rects {
+                edges.extend([from, to + 1]);
+            }
+            edges.retain(|&edge| edge <= size);
+            edges.sort_unstable();
+            edges.dedup();
+            edges
+        };
+        let xs = edges(width, &mut commands.iter().map(|c| (c.x1, c.x2)));
+        let ys = edges(height, &mut commands.iter().map(|c| (c.y1, c.y2)));
+
+        let cells = (xs.len() - 1) * (ys.len() - 1);
+        CompressedGrid {
+            xs,
+            ys,
+            model,
+            states: vec![M::State::default(); cells],
+        }
+    }
+
+    // the cells spanning `from..=to` along `edges`
+    fn span(edges: &[usize], from: usize, to: usize) -> (usize, usize) {
+        let cell = |edge| {
+            edges
+                .binary_search(&edge)
+                .expect("Rectangle edges must be known when the grid is built")
+        };
+        (cell(from), cell(to + 1) - 1)
+    }
+
+    // every cell with the number of lights in it
+    fn cells(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        let columns = self.xs.len() - 1;
+        (0..self.states.len()).map(move |cell| {
+            let (cx, cy) = (cell % columns, cell / columns);
+            let width = self.xs[cx + 1] - self.xs[cx];
+            let height = self.ys[cy + 1] - self.ys[cy];
+            (cell, (width * height) as u64)
+        })
+    }
+}
+
+impl<M: LightModel> LightGrid<M> {
+    fn new(width: usize, height: usize, model: M) -> LightGrid<M> {
+        LightGrid {
+            width,
+            height,
+            model,
+            cells: Cells::new(width, height),
+        }
+    }
+}
+
+impl LightGrid<Brightness> {
+    fn peak(&self) -> u64 {
+        self.cells.states.iter().copied().max().unwrap_or(0) as u64
+    }
+
+    // The brightness of every light, row by row, scaled so `peak` is the
+    // white of the returned maxval. Brighter lights are white too.
+    fn image(&self, peak: u64) -> (u16, Vec<u16>) {
+        let peak = peak.max(1);
+        let maxval = peak.min(u16::MAX as u64);
+        let pixels = self
+            .cells
+            .states
+            .iter()
+            .map(|&brightness| ((brightness as u64).min(peak) * maxval / peak) as u16)
+            .collect();
+        (maxval as u16, pixels)
+    }
+}
+
+impl LightGrid<OnOff> {
+    #[cfg(test)]
+    fn is_lit(&self, x: usize, y: usize) -> bool {
+        self.cells.words[y * self.cells.words_per_row + x / 64] >> (x % 64) & 1 == 1
+    }
+}
+
+impl<M: LightModel> DroneGrid<M> {
+    fn new(width: usize, height: usize, model: M) -> DroneGrid<M> {
         DroneGrid {
             width,
             height,
+            model,
             grid: vec![
                 vec![
                     Drone {
                         x: 0,
                         y: 0,
-                        led_on: false,
-                        brightness: 0
+                        state: M::State::default()
                     };
                     width
                 ];
@@ -155,65 +1239,176 @@ impl DroneGrid {
             ],
         }
     }
+}
 
-    fn turn_on_rect(self: &mut DroneGrid, x1: usize, y1: usize, x2: usize, y2: usize) {
-        for y in y1..=y2 {
-            for x in x1..=x2 {
-                self.grid[y][x].turn_on();
-            }
+fn parse_instructions(input: &str, syntax: &Syntax) -> Result<Vec<DroneCommand>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            parse_drone_command(line, syntax).map_err(|(column, message)| ParseError {
+                line: i + 1,
+                column,
+                message,
+            })
+        })
+        .collect()
+}
+
+// `turn on|turn off|toggle X,Y through X,Y`, plus `set N` and `invert` in
+// place of the verb when `syntax` is extended. Fails with the column of the
+// offending token and what was expected there.
+fn parse_drone_command(line: &str, syntax: &Syntax) -> Result<DroneCommand, (usize, String)> {
+    let verbs = if syntax.extended {
+        "`turn`, `toggle`, `set` or `invert`"
+    } else {
+        "`turn` or `toggle`"
+    };
+
+    let mut tokens = Tokens::new(line);
+    let (column, verb) = tokens.next(verbs)?;
+    let action = match verb {
+        "turn" => match tokens.next("`on` or `off`")? {
+            (_, "on") => Action::TurnOn,
+            (_, "off") => Action::TurnOff,
+            (column, found) => return Err(unexpected(column, "`on` or `off`", Some(found))),
+        },
+        "toggle" => Action::Toggle,
+        "set" if syntax.extended => Action::Set(tokens.number("a brightness")?.0),
+        "invert" if syntax.extended => Action::Invert,
+        "set" | "invert" => {
+            return Err((
+                column,
+                format!("`{}` is an extended instruction (see --extended)", verb),
+            ))
+        }
+        _ => return Err(unexpected(column, verbs, Some(verb))),
+    };
+
+    let (x1, y1) = tokens.point()?;
+    tokens.expect("through")?;
+    let (x2, y2) = tokens.point()?;
+    tokens.finish()?;
+
+    // the corners come back with their columns, so a bad one can be pointed at
+    let (width, height) = match syntax.size {
+        Some((width, height)) => (Some(width), Some(height)),
+        None => (None, None),
+    };
+    for ((from, _), (to, column), axis, size) in [(x1, x2, "x", width), (y1, y2, "y", height)] {
+        if to < from {
+            return Err((
+                column,
+                format!("{}2 ({}) is less than {}1 ({})", axis, to, axis, from),
+            ));
+        }
+        if let Some(size) = size.filter(|&size| to >= size) {
+            return Err((
+                column,
+                format!("{}2 ({}) is outside the grid (0 to {})", axis, to, size - 1),
+            ));
+        }
+        // a fitted grid is one light wider than its furthest corner
+        if size.is_none() && to == usize::MAX {
+            return Err((column, format!("{} is too large", to)));
         }
     }
 
-    fn turn_off_rect(self: &mut DroneGrid, x1: usize, y1: usize, x2: usize, y2: usize) {
-        for y in y1..=y2 {
-            for x in x1..=x2 {
-                self.grid[y][x].turn_off();
+    Ok(DroneCommand {
+        action,
+        x1: x1.0,
+        y1: y1.0,
+        x2: x2.0,
+        y2: y2.0,
+    })
+}
+
+fn unexpected(column: usize, expected: &str, found: Option<&str>) -> (usize, String) {
+    match found {
+        Some(found) => (column, format!("expected {}, found {:?}", expected, found)),
+        None => (column, format!("expected {}, found end of line", expected)),
+    }
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str) -> Tokens<'a> {
+        let mut tokens = Vec::new();
+        let mut chars = line.char_indices().zip(1..).peekable();
+        while let Some(((start, c), column)) = chars.next() {
+            if c == ' ' || c == '\t' {
+                continue;
+            }
+            let mut end = start + c.len_utf8();
+            if c.is_alphanumeric() {
+                while let Some(&((i, c), _)) = chars.peek() {
+                    if !c.is_alphanumeric() {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
             }
+            tokens.push((column, &line[start..end]));
+        }
+
+        Tokens {
+            tokens,
+            next: 0,
+            end: line.chars().count() + 1,
         }
     }
 
-    fn toggle_rect(self: &mut DroneGrid, x1: usize, y1: usize, x2: usize, y2: usize) {
-        for y in y1..=y2 {
-            for x in x1..=x2 {
-                self.grid[y][x].toggle();
-            }
+    fn next(&mut self, expected: &str) -> Result<(usize, &'a str), (usize, String)> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .copied()
+            .ok_or_else(|| unexpected(self.end, expected, None))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), (usize, String)> {
+        let expected = format!("`{}`", word);
+        match self.next(&expected)? {
+            (_, token) if token == word => Ok(()),
+            (column, token) => Err(unexpected(column, &expected, Some(token))),
         }
     }
 
-    fn count_lit(self: &mut DroneGrid) -> u64 {
-        let mut count = 0;
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if self.grid[y][x].led_on {
-                    count += 1;
-                }
-            }
+    fn number<T: FromStr>(&mut self, what: &str) -> Result<(T, usize), (usize, String)> {
+        let (column, token) = self.next(what)?;
+        if !token.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(unexpected(column, what, Some(token)));
         }
-        count
+        let number = token
+            .parse()
+            .map_err(|_| (column, format!("{} is too large", token)))?;
+        Ok((number, column))
     }
 
-    fn brightness(self: &mut DroneGrid) -> u64 {
-        let mut total_brightness = 0;
-        for y in 0..self.height {
-            for x in 0..self.width {
-                total_brightness += self.grid[y][x].brightness;
-            }
+    // `X,Y`, each coordinate with its column
+    fn point(&mut self) -> Result<(Coordinate, Coordinate), (usize, String)> {
+        let x = self.number("a coordinate")?;
+        self.expect(",")?;
+        let y = self.number("a coordinate")?;
+        Ok((x, y))
+    }
+
+    fn finish(&self) -> Result<(), (usize, String)> {
+        match self.tokens.get(self.next) {
+            Some(&(column, token)) => Err(unexpected(column, "end of line", Some(token))),
+            None => Ok(()),
         }
-        total_brightness
     }
 }
 
-fn parse_drone_command(string: &str) -> Result<DroneCommand, ()> {
-    let re = Regex::new(r"^(turn on|turn off|toggle) (\d+),(\d+) through (\d+),(\d+)$").unwrap();
-    for (_, [command, x1, y1, x2, y2]) in re.captures_iter(string).map(|c| c.extract()) {
-        return Ok(DroneCommand {
-            command: command.to_string(),
-            x1: x1.parse::<usize>().unwrap(),
-            y1: y1.parse::<usize>().unwrap(),
-            x2: x2.parse::<usize>().unwrap(),
-            y2: y2.parse::<usize>().unwrap(),
-        });
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
     }
-
-    Err(())
 }
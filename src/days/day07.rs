@@ -1,25 +1,99 @@
+use crate::etc::validate::{self, Violation};
+use crate::etc::{config, trace};
 use crate::{Solution, SolutionPair};
-use std::fs::read_to_string;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
 
 ///////////////////////////////////////////////////////////////////////////////
 
 pub fn solve() -> SolutionPair {
-    let input = read_to_string("input/day07.txt").expect("couldn't read input file");
+    let input = trace::in_span("parse", || config::read_input(7));
 
-    let circuit = driver(&input.to_string());
+    let mut circuit = trace::in_span("build", || driver(&input));
+    let sol1 = trace::in_span("part1", || circuit.get_final_signal_value());
 
-    (
-        Solution::from(circuit.get_final_signal_value()),
-        Solution::from(0),
-    )
+    (Solution::from(sol1), Solution::from(0))
+}
+
+pub fn validate(input: &str) -> Vec<Violation> {
+    validate::check_lines(input, |line| {
+        let (expression, wire) = line
+            .split_once(" -> ")
+            .ok_or((1, "expected `<expression> -> <wire>`".to_string()))?;
+
+        if !is_wire(wire) {
+            let column = expression.len() + 5;
+            return Err((column, format!("expected a wire name, found {:?}", wire)));
+        }
+
+        let tokens: Vec<&str> = expression.split(' ').collect();
+        let operand_ok = |token: &str| token.parse::<Operand>().is_ok();
+        let valid = match tokens.as_slice() {
+            [value] => operand_ok(value),
+            ["NOT", value] => operand_ok(value),
+            [left, "AND" | "OR", right] => operand_ok(left) && operand_ok(right),
+            [value, "LSHIFT" | "RSHIFT", amount] if operand_ok(value) => {
+                if shift_amount(amount).is_none() {
+                    let column = expression.len() - amount.len() + 1;
+                    return Err((
+                        column,
+                        format!("expected a shift amount from 0 to 15, found {:?}", amount),
+                    ));
+                }
+                true
+            }
+            _ => false,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err((
+                1,
+                format!("expected a gate expression, found {:?}", expression),
+            ))
+        }
+    })
+}
+
+fn is_wire(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_lowercase())
+}
+
+// signals are 16 bits wide, so only shifts by 0 to 15 keep any of them
+fn shift_amount(token: &str) -> Option<u16> {
+    token.parse().ok().filter(|&amount| amount < 16)
 }
 
 #[test]
 fn test() {
+    use std::fs::read_to_string;
+
     let input =
         read_to_string("input/test/day07.txt").expect("Expected to find test input file for day 7");
 
-    let circuit = driver(&input);
+    assert!(validate(&input).is_empty());
+    assert_eq!(
+        validate("123 -> x\nx XOR y -> z\n1 -> Q\n"),
+        vec![
+            Violation::new(2, 1, "expected a gate expression, found \"x XOR y\""),
+            Violation::new(3, 6, "expected a wire name, found \"Q\""),
+        ]
+    );
+    assert_eq!(
+        validate("x LSHIFT 15 -> y\nx RSHIFT 16 -> z\n"),
+        vec![Violation::new(
+            2,
+            10,
+            "expected a shift amount from 0 to 15, found \"16\""
+        )]
+    );
+    let mut shifted = driver("32769 -> x\nx LSHIFT 15 -> y\nx RSHIFT 15 -> z\n");
+    assert_eq!(shifted.get_signal("y"), Ok(1 << 15));
+    assert_eq!(shifted.get_signal("z"), Ok(1));
+
+    let mut circuit = driver(&input);
 
     assert_eq!(
         circuit.get_signal("d").expect("expected d to have value"),
@@ -53,21 +127,70 @@ fn test() {
         circuit.get_signal("y").expect("expected y to have value"),
         456
     );
+
+    // wires can be used before the line driving them
+    let reversed: Vec<&str> = input.lines().rev().collect();
+    let mut circuit = driver(&reversed.join("\n"));
+    assert_eq!(circuit.get_signal("d"), Ok(72));
+    assert_eq!(circuit.get_signal("i"), Ok(65079));
+
+    let mut circuit = driver("NOT c -> a\nb AND b -> c\na OR a -> b\nNOT q -> d");
+    assert_eq!(
+        circuit.get_signal("a"),
+        Err(CircuitError::Loop("a".to_string()))
+    );
+    assert_eq!(
+        circuit.get_signal("d"),
+        Err(CircuitError::Undriven("q".to_string()))
+    );
+    assert_eq!(
+        circuit.get_signal("e"),
+        Err(CircuitError::Undriven("e".to_string()))
+    );
+
+    // literals and wires anywhere an operand goes
+    let mut circuit =
+        driver("1 AND cx -> cy\ncy OR 6 -> a\nNOT 0 -> b\n5 LSHIFT 2 -> c\nlx -> cx\n3 -> lx");
+    assert_eq!(circuit.get_signal("cx"), Ok(3));
+    assert_eq!(circuit.get_signal("cy"), Ok(1));
+    assert_eq!(circuit.get_signal("a"), Ok(7));
+    assert_eq!(circuit.get_signal("b"), Ok(u16::MAX));
+    assert_eq!(circuit.get_signal("c"), Ok(20));
+    assert_eq!("cx".parse(), Ok(Operand::Wire("cx".to_string())));
+    assert_eq!("65535".parse(), Ok(Operand::Literal(65535)));
+    assert!("65536".parse::<Operand>().is_err());
+    assert!("Cx".parse::<Operand>().is_err());
+
+    // long chains are evaluated without recursing
+    // (wire names are lowercase, so wire i is named by its digits as letters)
+    let wire = |i: usize| -> String {
+        i.to_string()
+            .bytes()
+            .map(|digit| (digit - b'0' + b'a') as char)
+            .collect()
+    };
+    let chain: Vec<String> = (0..100_000)
+        .map(|i| format!("NOT {} -> {}", wire(i + 1), wire(i)))
+        .chain([format!("1 -> {}", wire(100_000))])
+        .collect();
+    let mut circuit = driver(&chain.join("\n"));
+    assert_eq!(circuit.get_signal(&wire(0)), Ok(1));
 }
 
-fn driver(input: &String) -> Circuit {
+// The circuit of every line, in any order. Signals are only worked out when
+// asked for.
+fn driver(input: &str) -> Circuit {
     let mut circuit = Circuit::new();
 
     for line in input.lines() {
-        let command = parse_command(&line.to_string());
-        println!("{:?}", line);
-        command.execute(&mut circuit);
+        let command = trace::in_span("parse_command", || parse_command(line));
+        circuit.connect(command);
     }
 
     circuit
 }
 
-fn parse_command(line: &String) -> Box<dyn Command> {
+fn parse_command(line: &str) -> Box<dyn Command> {
     let mut parts: std::str::Split<'_, &str> = line.split(" -> ");
     let command: &str = parts.next().expect("Expected a command");
     let wire_name: &str = parts.next().expect("Expected a wire name");
@@ -76,247 +199,312 @@ fn parse_command(line: &String) -> Box<dyn Command> {
 
     if command_parts.len() == 1 {
         return Box::new(ValueCommand {
-            value: command_parts[0]
-                .to_string()
-                .parse()
-                .expect("Expected a number"),
+            value: operand(command_parts[0]),
             wire_name: wire_name.to_string(),
         });
     }
 
     if command_parts.len() == 2 {
         return Box::new(NotCommand {
-            target_wire_id: command_parts[1].to_string(),
+            target: operand(command_parts[1]),
             dest_wire_id: wire_name.to_string(),
         });
     }
 
     match command_parts[1] {
         "AND" => Box::new(AndCommand {
-            left_target_id: command_parts[0].to_string(),
-            right_target_id: command_parts[2].to_string(),
+            left: operand(command_parts[0]),
+            right: operand(command_parts[2]),
             dest_wire_id: wire_name.to_string(),
         }),
         "OR" => Box::new(OrCommand {
-            left_target_id: command_parts[0].to_string(),
-            right_target_id: command_parts[2].to_string(),
+            left: operand(command_parts[0]),
+            right: operand(command_parts[2]),
             dest_wire_id: wire_name.to_string(),
         }),
         "LSHIFT" => Box::new(LShiftCommand {
-            target_wire_id: command_parts[0].to_string(),
-            shift_amount: command_parts[2]
-                .to_string()
-                .parse()
-                .expect("Expected a number"),
+            target: operand(command_parts[0]),
+            shift_amount: shift(command_parts[2]),
             dest_wire_id: wire_name.to_string(),
         }),
         "RSHIFT" => Box::new(RShiftCommand {
-            target_wire_id: command_parts[0].to_string(),
-            shift_amount: command_parts[2]
-                .to_string()
-                .parse()
-                .expect("Expected a number"),
+            target: operand(command_parts[0]),
+            shift_amount: shift(command_parts[2]),
             dest_wire_id: wire_name.to_string(),
         }),
         _ => panic!("Unknown command"),
     }
 }
 
-struct Wire {
-    identifier: String,
-    signal: u16,
+fn operand(token: &str) -> Operand {
+    token.parse().unwrap_or_else(|e| panic!("{}", e))
 }
 
+fn shift(token: &str) -> u16 {
+    shift_amount(token)
+        .unwrap_or_else(|| panic!("Expected a shift amount from 0 to 15, found {:?}", token))
+}
+
+// The wires of a circuit, each driven by the command whose output it is. The
+// signal of a wire is worked out from its inputs the first time it is asked
+// for and kept from then on.
 struct Circuit {
-    wires: Vec<Wire>,
+    commands: HashMap<String, Box<dyn Command>>,
+    signals: HashMap<String, u16>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum CircuitError {
+    // no command outputs to the wire
+    Undriven(String),
+    // the wire's signal depends on itself
+    Loop(String),
 }
 
 impl Circuit {
     fn new() -> Self {
-        Self { wires: Vec::new() }
+        Self {
+            commands: HashMap::new(),
+            signals: HashMap::new(),
+        }
     }
 
-    fn get_signal(&self, wire_name: &str) -> Option<u16> {
-        for wire in self.wires.iter() {
-            if wire.identifier == wire_name {
-                return Some(wire.signal);
+    // a wire driven twice keeps its last command
+    fn connect(&mut self, command: Box<dyn Command>) {
+        self.signals.clear();
+        self.commands.insert(command.wire().to_string(), command);
+    }
+
+    // Works out the signal of `wire` depth first, with a stack instead of
+    // recursion so long chains of gates can't overflow. The wires on the stack
+    // that are waiting for their inputs are the path down from `wire`, so
+    // meeting one of them again means a loop.
+    fn get_signal(&mut self, wire: &str) -> Result<u16, CircuitError> {
+        let mut stack = vec![wire.to_string()];
+        let mut waiting = HashSet::new();
+
+        while let Some(top) = stack.last() {
+            if self.signals.contains_key(top) {
+                stack.pop();
+                continue;
+            }
+
+            let command = self
+                .commands
+                .get(top)
+                .ok_or_else(|| CircuitError::Undriven(top.clone()))?;
+            let missing: Vec<&str> = command
+                .operands()
+                .into_iter()
+                .filter_map(Operand::wire)
+                .filter(|input| !self.signals.contains_key(*input))
+                .collect();
+
+            if missing.is_empty() {
+                let operands: Vec<u16> = command
+                    .operands()
+                    .into_iter()
+                    .map(|operand| match operand {
+                        Operand::Wire(input) => self.signals[input],
+                        Operand::Literal(signal) => *signal,
+                    })
+                    .collect();
+                let signal = command.evaluate(&operands);
+                waiting.remove(top);
+                self.signals.insert(top.clone(), signal);
+                stack.pop();
+            } else {
+                if let Some(input) = missing.iter().find(|input| waiting.contains(**input)) {
+                    return Err(CircuitError::Loop(input.to_string()));
+                }
+                waiting.insert(top.clone());
+                stack.extend(missing.into_iter().map(str::to_string));
             }
         }
 
-        None
+        Ok(self.signals[wire])
     }
 
     // the "a" labeled wire is defined by the problem to be the final value
     // that our code is searching for, this function is a wrapper around
     // `get_signal` to make that process more clear
-    fn get_final_signal_value(&self) -> u16 {
-        self.get_signal("a")
-            .expect("expected final value to have 'a' wire")
+    fn get_final_signal_value(&mut self) -> u16 {
+        self.get_signal("a").unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CircuitError::Undriven(wire) => write!(f, "Nothing drives wire {}", wire),
+            CircuitError::Loop(wire) => write!(f, "Wire {} depends on itself", wire),
+        }
+    }
+}
+
+// A gate, or a signal given directly, driving one wire.
 trait Command {
-    fn execute(&self, circuit: &mut Circuit);
+    // the wire it drives
+    fn wire(&self) -> &str;
+    // what it reads, in the order `evaluate` takes their signals
+    fn operands(&self) -> Vec<&Operand>;
+    fn evaluate(&self, operands: &[u16]) -> u16;
+}
+
+// The input of a gate, or the source of a wire set directly: the signal of
+// another wire or a number.
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Wire(String),
+    Literal(u16),
+}
+
+impl Operand {
+    fn wire(&self) -> Option<&str> {
+        match self {
+            Operand::Wire(wire) => Some(wire),
+            Operand::Literal(_) => None,
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(token: &str) -> Result<Operand, String> {
+        if is_wire(token) {
+            Ok(Operand::Wire(token.to_string()))
+        } else {
+            token
+                .parse()
+                .map(Operand::Literal)
+                .map_err(|_| format!("Expected a wire or a signal, found {:?}", token))
+        }
+    }
 }
 
 #[derive(Debug)]
 struct ValueCommand {
-    value: u16,
+    value: Operand,
     wire_name: String,
 }
 
 impl Command for ValueCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.wire_name {
-                wire.signal = self.value;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.wire_name
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.wire_name.clone(),
-            signal: self.value,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.value]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        operands[0]
     }
 }
 
 #[derive(Debug)]
 struct NotCommand {
-    target_wire_id: String,
+    target: Operand,
     dest_wire_id: String,
 }
 
 impl Command for NotCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        let signal: u16 = circuit
-            .get_signal(&self.target_wire_id)
-            .expect("Expected a valid signal");
-
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.dest_wire_id {
-                wire.signal = !signal;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.dest_wire_id
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.dest_wire_id.clone(),
-            signal: !signal,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.target]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        !operands[0]
     }
 }
 
 #[derive(Debug)]
 struct AndCommand {
-    left_target_id: String,
-    right_target_id: String,
+    left: Operand,
+    right: Operand,
     dest_wire_id: String,
 }
 
 impl Command for AndCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        let left_signal: u16 = circuit
-            .get_signal(&self.left_target_id)
-            .expect("Expected a valid signal");
-        let right_signal: u16 = circuit
-            .get_signal(&self.right_target_id)
-            .expect("Expected a valid signal");
-
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.dest_wire_id {
-                wire.signal = left_signal & right_signal;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.dest_wire_id
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.dest_wire_id.clone(),
-            signal: left_signal & right_signal,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.left, &self.right]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        operands[0] & operands[1]
     }
 }
 
 #[derive(Debug)]
 struct OrCommand {
-    left_target_id: String,
-    right_target_id: String,
+    left: Operand,
+    right: Operand,
     dest_wire_id: String,
 }
 
 impl Command for OrCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        let left_signal: u16 = circuit
-            .get_signal(&self.left_target_id)
-            .expect("Expected a valid signal");
-        let right_signal: u16 = circuit
-            .get_signal(&self.right_target_id)
-            .expect("Expected a valid signal");
-
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.dest_wire_id {
-                wire.signal = left_signal | right_signal;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.dest_wire_id
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.dest_wire_id.clone(),
-            signal: left_signal | right_signal,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.left, &self.right]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        operands[0] | operands[1]
     }
 }
 
 #[derive(Debug)]
 struct LShiftCommand {
-    target_wire_id: String,
+    target: Operand,
     dest_wire_id: String,
+    // 0 to 15, checked by `shift`
     shift_amount: u16,
 }
 
 impl Command for LShiftCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        let signal: u16 = circuit
-            .get_signal(&self.target_wire_id)
-            .expect("Expected a valid signal");
-
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.dest_wire_id {
-                wire.signal = signal << self.shift_amount;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.dest_wire_id
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.dest_wire_id.clone(),
-            signal: signal << self.shift_amount,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.target]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        operands[0] << self.shift_amount
     }
 }
 
 #[derive(Debug)]
 struct RShiftCommand {
-    target_wire_id: String,
+    target: Operand,
     dest_wire_id: String,
+    // 0 to 15, checked by `shift`
     shift_amount: u16,
 }
 
 impl Command for RShiftCommand {
-    fn execute(&self, circuit: &mut Circuit) {
-        let signal: u16 = circuit
-            .get_signal(&self.target_wire_id)
-            .expect("Expected a valid signal");
-
-        for wire in circuit.wires.iter_mut() {
-            if wire.identifier == self.dest_wire_id {
-                wire.signal = signal >> self.shift_amount;
-                return;
-            }
-        }
+    fn wire(&self) -> &str {
+        &self.dest_wire_id
+    }
 
-        circuit.wires.push(Wire {
-            identifier: self.dest_wire_id.clone(),
-            signal: signal >> self.shift_amount,
-        });
+    fn operands(&self) -> Vec<&Operand> {
+        vec![&self.target]
+    }
+
+    fn evaluate(&self, operands: &[u16]) -> u16 {
+        operands[0] >> self.shift_amount
     }
 }
@@ -1,3 +1,6 @@
+// days 8 to 25 are still the template, unused imports included
+#![allow(unused_imports)]
+
 pub mod day01;
 pub mod day02;
 pub mod day03;
@@ -0,0 +1,152 @@
+use std::fmt::{Display, Formatter, Result};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Heap accounting for the `alloc-stats` cargo feature. With the feature on,
+// every allocation goes through `CountingAlloc`, which keeps the bytes in use,
+// their high-water mark and the number of allocations. The counters are
+// process-wide, so scopes that overlap across threads see each other's
+// allocations.
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The spans whose heap usage is reported for every day.
+pub const PARTS: [&str; 2] = ["part1", "part2"];
+
+// the usage of each of `PARTS` since the last `take_parts`, kept in place so
+// that recording it doesn't allocate
+static PART_STATS: Mutex<[Option<AllocStats>; 2]> = Mutex::new([None, None]);
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use super::{COUNT, CURRENT, PEAK};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::Ordering;
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    pub struct CountingAlloc;
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+                record_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+
+    fn record_alloc(size: usize) {
+        COUNT.fetch_add(1, Ordering::Relaxed);
+        let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+}
+
+/// Whether the binary was built with the counting allocator.
+pub const ENABLED: bool = cfg!(feature = "alloc-stats");
+
+/// Heap usage over a scope: the peak bytes in use above what was already
+/// allocated when the scope began, and the number of allocations made.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AllocStats {
+    pub peak_bytes: usize,
+    pub allocations: u64,
+}
+
+/// Open measurement returned by `begin`. Scopes may nest as long as they are
+/// closed in reverse order.
+pub struct Scope {
+    baseline: usize,
+    allocations: u64,
+    outer_peak: usize,
+}
+
+pub fn begin() -> Scope {
+    let current = CURRENT.load(Ordering::Relaxed);
+    Scope {
+        baseline: current,
+        allocations: COUNT.load(Ordering::Relaxed),
+        outer_peak: PEAK.swap(current, Ordering::Relaxed),
+    }
+}
+
+pub fn end(scope: Scope) -> AllocStats {
+    // hand the enclosing scope back the larger of both high-water marks
+    let peak = PEAK.fetch_max(scope.outer_peak, Ordering::Relaxed);
+    AllocStats {
+        peak_bytes: peak.saturating_sub(scope.baseline),
+        allocations: COUNT.load(Ordering::Relaxed) - scope.allocations,
+    }
+}
+
+/// Adds the usage of a `PARTS[part]` span. A part measured more than once
+/// keeps its largest peak and the sum of its allocations.
+pub fn record_part(part: usize, stats: AllocStats) {
+    let mut parts = PART_STATS.lock().unwrap();
+    parts[part] = Some(match parts[part] {
+        Some(recorded) => AllocStats {
+            peak_bytes: recorded.peak_bytes.max(stats.peak_bytes),
+            allocations: recorded.allocations + stats.allocations,
+        },
+        None => stats,
+    });
+}
+
+/// Removes and returns the usage of each of `PARTS` recorded so far.
+pub fn take_parts() -> [Option<AllocStats>; 2] {
+    std::mem::take(&mut *PART_STATS.lock().unwrap())
+}
+
+impl Display for AllocStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{} peak, {} allocations",
+            format_bytes(self.peak_bytes),
+            self.allocations
+        )
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.2} {}", value, UNITS[unit]),
+    }
+}
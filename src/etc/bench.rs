@@ -0,0 +1,54 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+// Helpers for the `bench <days...>` subcommand. Each day that has a benchmark
+// generates its own workload and compares its implementations with `measure`.
+
+const RUNS: usize = 3;
+
+/// Runs `f` a few times and prints the best time. When `bytes` is non-zero
+/// the throughput over that many bytes of input is printed as well.
+pub fn measure<T>(label: &str, bytes: usize, mut f: impl FnMut() -> T) -> T {
+    let mut best = f64::INFINITY;
+    let mut result = None;
+
+    for _ in 0..RUNS {
+        let time = Instant::now();
+        let value = black_box(f());
+        best = best.min(time.elapsed().as_secs_f64());
+        result = Some(value);
+    }
+
+    match bytes {
+        0 => println!("  · {:<32} {:>12.4} ms", label, best * 1000.0),
+        _ => println!(
+            "  · {:<32} {:>12.4} ms {:>10.1} MiB/s",
+            label,
+            best * 1000.0,
+            bytes as f64 / (1024.0 * 1024.0) / best
+        ),
+    }
+
+    result.unwrap()
+}
+
+/// A small deterministic xorshift generator for benchmark inputs.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
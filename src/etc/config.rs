@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Runner settings. Every setting is resolved from, in increasing order of
+// precedence:
+//
+//   1. the defaults below
+//   2. `aoc.toml` in the project root (or the file named by `AOC_CONFIG`)
+//   3. `AOC_*` environment variables
+//   4. command-line flags
+//
+// Day solutions read their input through `read_input` and their tunables
+// through `day_option`, so both follow the same precedence.
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+const DEFAULT_FILE: &str = "aoc.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub year: u16,
+    pub input_dir: String,
+    pub format: Format,
+    pub timeout: Option<Duration>,
+    pub days: Vec<u8>,
+    // day 0 holds options that apply to every day, they come from flags
+    options: HashMap<u8, HashMap<String, String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            year: 2015,
+            input_dir: "input".to_string(),
+            format: Format::Text,
+            timeout: None,
+            days: (1..=25).collect(),
+            options: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the defaults, the config file and the environment. Flags are
+    /// applied on top with `apply_flag`.
+    pub fn load() -> Result<Config, String> {
+        let mut config = Config::default();
+
+        let path = env::var("AOC_CONFIG").ok();
+        match read_to_string(path.as_deref().unwrap_or(DEFAULT_FILE)) {
+            Ok(text) => config.apply_file(&text)?,
+            // only a missing default file is fine, an explicit one must exist
+            Err(e) if path.is_some() => {
+                return Err(format!("Could not read {}: {}", path.unwrap(), e));
+            }
+            Err(_) => {}
+        }
+
+        config.apply_env(env::vars())?;
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, text: &str) -> Result<(), String> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e| format!("{}: {}", DEFAULT_FILE, e))?;
+
+        for (key, value) in table {
+            match key.as_str() {
+                "day" => {
+                    let days = value.as_table().ok_or("`day` must be a table of days")?;
+                    for (day, options) in days {
+                        let day = parse_day(day)?;
+                        let options = options
+                            .as_table()
+                            .ok_or(format!("`day.{}` must be a table", day))?;
+                        for (name, value) in options {
+                            let value = match value {
+                                toml::Value::String(s) => s.clone(),
+                                other => other.to_string(),
+                            };
+                            self.set_option(day, name, &value);
+                        }
+                    }
+                }
+                "days" => {
+                    let days = value.as_array().ok_or("`days` must be an array")?;
+                    self.days = days
+                        .iter()
+                        .map(|d| parse_day(&d.to_string()))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => {
+                    let value = match value {
+                        toml::Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    if !self.apply_setting(&key, &value)? {
+                        return Err(format!("{}: unknown setting `{}`", DEFAULT_FILE, key));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_env(&mut self, vars: impl Iterator<Item = (String, String)>) -> Result<(), String> {
+        for (name, value) in vars {
+            let Some(name) = name.strip_prefix("AOC_") else {
+                continue;
+            };
+            let name = name.to_lowercase();
+
+            // AOC_DAY04_KEY=abc overrides `key` for day 4
+            if let Some((day, option)) = name.strip_prefix("day").and_then(|x| x.split_once('_')) {
+                self.set_option(parse_day(day)?, option, &value);
+                continue;
+            }
+
+            match name.as_str() {
+                "config" => {}
+                "days" => {
+                    self.days = value
+                        .split(',')
+                        .map(|d| parse_day(d.trim()))
+                        .collect::<Result<_, _>>()?;
+                }
+                // other tools use the AOC_ prefix too (AOC_SESSION, ...)
+                _ => {
+                    self.apply_setting(&name, &value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a command-line flag. Runner settings are recognized by name,
+    /// any other flag becomes an option for every day being run.
+    pub fn apply_flag(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if !self.apply_setting(&name.replace('-', "_"), value)? {
+            self.set_option(0, name, value);
+        }
+        Ok(())
+    }
+
+    // returns whether `name` is a runner setting
+    fn apply_setting(&mut self, name: &str, value: &str) -> Result<bool, String> {
+        match name {
+            "year" => {
+                self.year = value
+                    .parse()
+                    .map_err(|_| format!("Not a valid year: {}", value))?
+            }
+            "input_dir" => self.input_dir = value.to_string(),
+            "format" => {
+                self.format = match value {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    _ => return Err(format!("Unknown format: {} (expected text or json)", value)),
+                }
+            }
+            "timeout" => {
+                let seconds: f64 = value
+                    .parse()
+                    .map_err(|_| format!("Not a valid timeout in seconds: {}", value))?;
+                self.timeout = (seconds > 0.0).then(|| Duration::from_secs_f64(seconds));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn set_option(&mut self, day: u8, name: &str, value: &str) {
+        self.options
+            .entry(day)
+            .or_default()
+            .insert(name.replace('_', "-"), value.to_string());
+    }
+
+    pub fn option(&self, day: u8, name: &str) -> Option<&str> {
+        [0, day]
+            .iter()
+            .find_map(|d| self.options.get(d)?.get(name))
+            .map(String::as_str)
+    }
+
+    pub fn input_path(&self, day: u8) -> String {
+        format!("{}/day{:02}.txt", self.input_dir, day)
+    }
+}
+
+fn parse_day(day: &str) -> Result<u8, String> {
+    match day.parse() {
+        Ok(day @ 1..=25) => Ok(day),
+        _ => Err(format!("Not a valid day: {}", day)),
+    }
+}
+
+/// Installs the resolved configuration, must be called at most once.
+pub fn init(config: Config) {
+    CONFIG
+        .set(config)
+        .expect("configuration was already initialized");
+}
+
+/// The active configuration, or the defaults if `init` was never called
+/// (as in unit tests).
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn day_option(day: u8, name: &str) -> Option<&'static str> {
+    get().option(day, name)
+}
+
+pub fn input_path(day: u8) -> String {
+    get().input_path(day)
+}
+
+pub fn read_input(day: u8) -> String {
+    let path = input_path(day);
+    read_to_string(&path).unwrap_or_else(|e| panic!("Could not read {}: {}", path, e))
+}
+
+#[test]
+fn test() {
+    let mut config = Config::default();
+    config
+        .apply_file(
+            "year = 2016\ntimeout = 2.5\ndays = [4, 6]\n[day.4]\nkey = \"abcdef\"\nstart = 10\n",
+        )
+        .unwrap();
+    assert_eq!(config.year, 2016);
+    assert_eq!(config.timeout, Some(Duration::from_millis(2500)));
+    assert_eq!(config.days, vec![4, 6]);
+    assert_eq!(config.option(4, "key"), Some("abcdef"));
+    assert_eq!(config.option(4, "start"), Some("10"));
+    assert_eq!(config.option(5, "key"), None);
+
+    let vars = [
+        ("AOC_INPUT_DIR", "stress"),
+        ("AOC_DAY04_KEY", "pqrstuv"),
+        ("AOC_DAYS", "1, 2"),
+        ("HOME", "/root"),
+    ];
+    config
+        .apply_env(vars.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+        .unwrap();
+    assert_eq!(config.input_path(3), "stress/day03.txt");
+    assert_eq!(config.option(4, "key"), Some("pqrstuv"));
+    assert_eq!(config.days, vec![1, 2]);
+
+    config.apply_flag("input-dir", "other").unwrap();
+    config.apply_flag("key", "xyz").unwrap();
+    config.apply_flag("format", "json").unwrap();
+    assert_eq!(config.input_path(3), "other/day03.txt");
+    assert_eq!(config.option(4, "key"), Some("xyz"));
+    assert_eq!(config.format, Format::Json);
+
+    assert!(config.apply_flag("timeout", "soon").is_err());
+    assert!(config.apply_file("colour = \"blue\"").is_err());
+    assert!(config.apply_file("[day.26]\nkey = 1").is_err());
+}
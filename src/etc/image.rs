@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Writers for the binary netpbm formats, which need no dependencies and open
+// in most image viewers (or convert with `magick out.ppm out.png`), and for
+// PNG, stored without compression for the same reason.
+
+pub type Rgb = [u8; 3];
+
+/// Writes `pixels`, row by row from the top left, as a binary PPM (P6) image.
+pub fn write_ppm(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    pixels: &[Rgb],
+) -> io::Result<()> {
+    let mut file = create(path, "P6", width, height, 255, pixels.len())?;
+    for pixel in pixels {
+        file.write_all(pixel)?;
+    }
+    file.flush()
+}
+
+/// Writes `pixels`, row by row from the top left, as a binary PGM (P5) image
+/// whose white is `maxval`. Values above 255 take two bytes per pixel.
+pub fn write_pgm(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    maxval: u16,
+    pixels: &[u16],
+) -> io::Result<()> {
+    let mut file = create(path, "P5", width, height, maxval, pixels.len())?;
+    for &pixel in pixels {
+        match maxval {
+            0..=255 => file.write_all(&[pixel as u8])?,
+            _ => file.write_all(&pixel.to_be_bytes())?,
+        }
+    }
+    file.flush()
+}
+
+/// Writes `pixels`, row by row from the top left, as a grayscale PNG whose
+/// white is `maxval`. Values above 255 give a 16-bit image.
+pub fn write_png(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    maxval: u16,
+    pixels: &[u16],
+) -> io::Result<()> {
+    let (depth, white) = match maxval {
+        0..=255 => (8, u8::MAX as u64),
+        _ => (16, u16::MAX as u64),
+    };
+    let mut samples = Vec::with_capacity(pixels.len() * depth as usize / 8);
+    for &pixel in pixels {
+        let value = pixel.min(maxval) as u64 * white / maxval.max(1) as u64;
+        match depth {
+            8 => samples.push(value as u8),
+            _ => samples.extend((value as u16).to_be_bytes()),
+        }
+    }
+    write_png_samples(path, width, height, (depth, 0), pixels.len(), &samples)
+}
+
+/// Writes `pixels`, row by row from the top left, as an 8-bit RGB PNG.
+pub fn write_rgb_png(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    pixels: &[Rgb],
+) -> io::Result<()> {
+    let samples = pixels.concat();
+    write_png_samples(path, width, height, (8, 2), pixels.len(), &samples)
+}
+
+// `samples` are the bytes of every pixel in turn, in the given bit depth and
+// PNG color type (0 for grayscale, 2 for RGB)
+fn write_png_samples(
+    path: impl AsRef<Path>,
+    width: usize,
+    height: usize,
+    (depth, color): (u8, u8),
+    pixels: usize,
+    samples: &[u8],
+) -> io::Result<()> {
+    assert_eq!(
+        pixels,
+        width * height,
+        "Expected {}x{} pixels",
+        width,
+        height
+    );
+
+    // every row starts with filter type 0, none
+    let row = samples.len() / height.max(1);
+    let mut raw = Vec::with_capacity(height + samples.len());
+    for y in 0..height {
+        raw.push(0);
+        raw.extend(&samples[y * row..(y + 1) * row]);
+    }
+
+    let mut header = Vec::new();
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // bit depth, color type, deflate, adaptive filters, not interlaced
+    header.extend([depth, color, 0, 0, 0]);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(&mut file, b"IHDR", &header)?;
+    write_chunk(&mut file, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(&mut file, b"IEND", &[])?;
+    file.flush()
+}
+
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    file.write_all(&crc.to_be_bytes())
+}
+
+// A zlib stream of uncompressed deflate blocks, which hold up to 65535 bytes
+// each
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        stream.push(blocks.peek().is_none() as u8);
+        stream.extend(len.to_le_bytes());
+        stream.extend((!len).to_le_bytes());
+        stream.extend(block);
+    }
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// the file with its header written, once `pixels` is known to fill the image
+fn create(
+    path: impl AsRef<Path>,
+    magic: &str,
+    width: usize,
+    height: usize,
+    maxval: u16,
+    pixels: usize,
+) -> io::Result<BufWriter<File>> {
+    assert_eq!(
+        pixels,
+        width * height,
+        "Expected {}x{} pixels",
+        width,
+        height
+    );
+
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "{}\n{} {}\n{}\n", magic, width, height, maxval)?;
+    Ok(file)
+}
+
+#[test]
+fn test() {
+    assert_eq!(crc32(b"IEND"), 0xae426082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    assert_eq!(
+        zlib_stored(b"abc"),
+        [0x78, 0x01, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27]
+    );
+    assert_eq!(
+        zlib_stored(&[]),
+        [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+    );
+
+    // blocks of at most 65535 bytes, only the last one final
+    let stream = zlib_stored(&[7; 70_000]);
+    assert_eq!(stream[2..7], [0, 0xff, 0xff, 0, 0]);
+    assert_eq!(
+        stream[7 + 65535..7 + 65535 + 5],
+        [1, 0x71, 0x11, 0x8e, 0xee]
+    );
+    assert_eq!(stream.len(), 2 + 5 + 65535 + 5 + 4465 + 4);
+
+    // a 2x1 RGB image: signature, header, then one row of a filter byte and
+    // 6 samples
+    let path = std::env::temp_dir().join(format!("aoc-image-{}.png", std::process::id()));
+    write_rgb_png(&path, 2, 1, &[[1, 2, 3], [4, 5, 6]]).unwrap();
+    let png = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+    assert_eq!(png[12..16], *b"IHDR");
+    assert_eq!(png[16..29], [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+    assert_eq!(png[37..41], *b"IDAT");
+    assert_eq!(png[41..43], [0x78, 0x01]);
+    assert_eq!(png[48..55], [0, 1, 2, 3, 4, 5, 6]);
+}
@@ -0,0 +1,632 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Hash-prefix mining: find the counters `n` for which `hash(key + n)` starts
+// with a given pattern. Counters are tried in the order `start`, `start +
+// step`, ... and the search returns the first matches in that order, however
+// many threads it runs on. MD5 is always available, SHA-1 and SHA-256 come
+// with the `sha1` and `sha2` cargo features.
+
+// counters claimed by a search thread at a time
+const BATCH: u64 = 4096;
+
+// how often progress is printed and checkpoints are saved
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A hash function the miner can run. The state after hashing the key is
+/// cloned for every counter, so the key is only hashed once.
+pub trait Hasher: Clone + Send + Sync {
+    type Digest: AsRef<[u8]>;
+
+    const NAME: &'static str;
+
+    fn new() -> Self;
+    fn update(&mut self, data: &[u8]);
+    fn finish(self) -> Self::Digest;
+}
+
+impl Hasher for md5::Context {
+    const NAME: &'static str = "md5";
+    type Digest = [u8; 16];
+
+    fn new() -> Self {
+        md5::Context::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.consume(data);
+    }
+
+    fn finish(self) -> [u8; 16] {
+        self.compute().0
+    }
+}
+
+#[cfg(feature = "sha1")]
+impl Hasher for sha1::Sha1 {
+    const NAME: &'static str = "sha1";
+    type Digest = [u8; 20];
+
+    fn new() -> Self {
+        <sha1::Sha1 as sha1::Digest>::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        sha1::Digest::update(self, data);
+    }
+
+    fn finish(self) -> [u8; 20] {
+        sha1::Digest::finalize(self).into()
+    }
+}
+
+#[cfg(feature = "sha2")]
+impl Hasher for sha2::Sha256 {
+    const NAME: &'static str = "sha256";
+    type Digest = [u8; 32];
+
+    fn new() -> Self {
+        <sha2::Sha256 as sha2::Digest>::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(self) -> [u8; 32] {
+        sha2::Digest::finalize(self).into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    #[cfg(feature = "sha1")]
+    Sha1,
+    #[cfg(feature = "sha2")]
+    Sha256,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Algorithm, String> {
+        match name {
+            "md5" => Ok(Algorithm::Md5),
+            #[cfg(feature = "sha1")]
+            "sha1" => Ok(Algorithm::Sha1),
+            #[cfg(feature = "sha2")]
+            "sha256" => Ok(Algorithm::Sha256),
+            #[cfg(not(feature = "sha1"))]
+            "sha1" => Err("sha1 needs the `sha1` feature".to_string()),
+            #[cfg(not(feature = "sha2"))]
+            "sha256" => Err("sha256 needs the `sha2` feature".to_string()),
+            _ => Err(format!("Unknown hash algorithm: {}", name)),
+        }
+    }
+}
+
+/// The leading bits a digest must have: the digest matches when its first
+/// bytes, masked with `mask`, equal `bytes`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+impl Pattern {
+    /// `zeros` leading zero hex digits.
+    pub fn zeros(zeros: usize) -> Pattern {
+        Pattern::hex(&"0".repeat(zeros)).unwrap()
+    }
+
+    /// A prefix of hex digits, e.g. `"000abc"`.
+    pub fn hex(digits: &str) -> Result<Pattern, String> {
+        let mut pattern = Pattern::default();
+        for (i, c) in digits.chars().enumerate() {
+            let nibble = c.to_digit(16).ok_or(format!("Not a hex digit: {:?}", c))? as u8;
+            pattern.push(i * 4, 4, nibble);
+        }
+        Ok(pattern)
+    }
+
+    /// A prefix of bits, e.g. `"0000000001"`.
+    pub fn bits(digits: &str) -> Result<Pattern, String> {
+        let mut pattern = Pattern::default();
+        for (i, c) in digits.chars().enumerate() {
+            let bit = c.to_digit(2).ok_or(format!("Not a bit: {:?}", c))? as u8;
+            pattern.push(i, 1, bit);
+        }
+        Ok(pattern)
+    }
+
+    // sets the `width` bits starting at bit `offset` to `value`
+    fn push(&mut self, offset: usize, width: usize, value: u8) {
+        if offset / 8 == self.bytes.len() {
+            self.bytes.push(0);
+            self.mask.push(0);
+        }
+        let shift = 8 - width - offset % 8;
+        let mask = ((1u16 << width) - 1) as u8;
+        self.bytes[offset / 8] |= value << shift;
+        self.mask[offset / 8] |= mask << shift;
+    }
+
+    pub fn matches(&self, digest: &[u8]) -> bool {
+        digest.len() >= self.bytes.len()
+            && self
+                .bytes
+                .iter()
+                .zip(&self.mask)
+                .zip(digest)
+                .all(|((&byte, &mask), &d)| d & mask == byte)
+    }
+}
+
+// the pattern's bytes and mask in hex, e.g. `0000a0/fffff0`
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.bytes {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, "/")?;
+        for byte in &self.mask {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// `0b...` is a bit pattern, anything else a hex one
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Pattern, String> {
+        match string.strip_prefix("0b") {
+            Some(bits) => Pattern::bits(bits),
+            None => Pattern::hex(string),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Miner {
+    key: Vec<u8>,
+    pattern: Pattern,
+    start: u64,
+    step: u64,
+    threads: usize,
+    progress: bool,
+    checkpoint: Option<PathBuf>,
+}
+
+// What the search threads share. Batches are numbered by their first index;
+// `searched` is the end of the prefix of batches that are all done, and
+// `completed` holds the done batches past it.
+struct Search {
+    next: AtomicU64,
+    bound: AtomicU64,
+    hashed: AtomicU64,
+    state: Mutex<SearchState>,
+}
+
+#[derive(Default)]
+struct SearchState {
+    found: Vec<u64>,
+    searched: u64,
+    completed: BTreeSet<u64>,
+}
+
+impl Miner {
+    /// Mines counters from 1 upwards on every available thread.
+    pub fn new(key: &str, pattern: Pattern) -> Miner {
+        Miner {
+            key: key.as_bytes().to_vec(),
+            pattern,
+            start: 1,
+            step: 1,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            progress: false,
+            checkpoint: None,
+        }
+    }
+
+    pub fn start(mut self, start: u64) -> Miner {
+        self.start = start;
+        self
+    }
+
+    pub fn step(mut self, step: u64) -> Miner {
+        assert!(step > 0, "The step must be positive");
+        self.step = step;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Miner {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Prints the hash rate and current counter to stderr while mining.
+    pub fn progress(mut self, progress: bool) -> Miner {
+        self.progress = progress;
+        self
+    }
+
+    /// Saves the highest fully searched counter to `path` every second, and
+    /// resumes from it when mining the same search again. The entry is
+    /// removed once the search completes.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>) -> Miner {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// The first `count` matching counters, in search order.
+    pub fn mine(&self, algorithm: Algorithm, count: usize) -> Vec<u64> {
+        match algorithm {
+            Algorithm::Md5 => self.mine_with::<md5::Context>(count),
+            #[cfg(feature = "sha1")]
+            Algorithm::Sha1 => self.mine_with::<sha1::Sha1>(count),
+            #[cfg(feature = "sha2")]
+            Algorithm::Sha256 => self.mine_with::<sha2::Sha256>(count),
+        }
+    }
+
+    // Threads claim batches of counter indices in increasing order. Once
+    // `count` matches are known, batches starting past the last of them are
+    // no longer claimed, so every index below it has been checked.
+    pub fn mine_with<H: Hasher>(&self, count: usize) -> Vec<u64> {
+        let digest_len = H::new().finish().as_ref().len();
+        assert!(
+            self.pattern.bytes.len() <= digest_len,
+            "The pattern is longer than a {}-byte digest",
+            digest_len
+        );
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let mut prefix = H::new();
+        prefix.update(&self.key);
+
+        let signature = self.signature(H::NAME, count);
+        let mut state = SearchState::default();
+        if let Some(path) = &self.checkpoint {
+            if let Some((searched, found)) = checkpoint::load(path, &signature) {
+                state.searched = self.index(searched);
+                state.found = found.iter().map(|&c| self.index(c)).collect();
+            }
+        }
+
+        let search = Search {
+            next: AtomicU64::new(state.searched),
+            bound: AtomicU64::new(if state.found.len() >= count {
+                state.found[count - 1]
+            } else {
+                u64::MAX
+            }),
+            hashed: AtomicU64::new(0),
+            state: Mutex::new(state),
+        };
+
+        let (done, finished) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|_| scope.spawn(|| self.search(&search, &prefix, count)))
+                .collect();
+            if self.progress || self.checkpoint.is_some() {
+                let (search, signature) = (&search, &signature);
+                scope.spawn(move || self.report(search, signature, finished));
+            }
+
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            drop(done);
+        });
+
+        if let Some(path) = &self.checkpoint {
+            checkpoint::save(path, &signature, None)
+                .unwrap_or_else(|e| eprintln!("Could not update {}: {}", path.display(), e));
+        }
+
+        let mut found = search.state.into_inner().unwrap().found;
+        found.sort_unstable();
+        found.truncate(count);
+        found.into_iter().map(|index| self.counter(index)).collect()
+    }
+
+    fn search<H: Hasher>(&self, search: &Search, prefix: &H, count: usize) {
+        let mut buffer = [0; 20];
+        loop {
+            let first = search.next.fetch_add(BATCH, Ordering::Relaxed);
+            if first >= search.bound.load(Ordering::Relaxed) {
+                return;
+            }
+
+            // the whole batch is scanned: stopping at the `count`th match
+            // would skip earlier matches a slower thread has yet to report
+            let mut matches = Vec::new();
+            for index in first..first + BATCH {
+                let mut hasher = prefix.clone();
+                hasher.update(decimal(self.counter(index), &mut buffer));
+                if self.pattern.matches(hasher.finish().as_ref()) {
+                    matches.push(index);
+                }
+            }
+            search.hashed.fetch_add(BATCH, Ordering::Relaxed);
+
+            let state = &mut *search.state.lock().unwrap();
+            state.found.extend(matches);
+            if state.found.len() >= count {
+                state.found.sort_unstable();
+                state.found.truncate(count);
+                search
+                    .bound
+                    .store(state.found[count - 1], Ordering::Relaxed);
+            }
+
+            state.completed.insert(first);
+            while state.completed.remove(&state.searched) {
+                state.searched += BATCH;
+            }
+        }
+    }
+
+    // runs until `finished` is disconnected, which happens when the search is
+    // over
+    fn report(&self, search: &Search, signature: &str, finished: mpsc::Receiver<()>) {
+        let began = Instant::now();
+        let mut saved = None;
+        let mut printed = false;
+
+        while let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(REPORT_INTERVAL) {
+            if self.progress {
+                let rate =
+                    search.hashed.load(Ordering::Relaxed) as f64 / began.elapsed().as_secs_f64();
+                let counter = self.counter(search.next.load(Ordering::Relaxed));
+                eprint!("\r  · {:>12.0} hashes/s, counter {:<20}", rate, counter);
+                printed = true;
+            }
+
+            if let Some(path) = &self.checkpoint {
+                let (searched, found) = {
+                    let state = search.state.lock().unwrap();
+                    let found: Vec<u64> = state
+                        .found
+                        .iter()
+                        .filter(|&&i| i < state.searched)
+                        .map(|&i| self.counter(i))
+                        .collect();
+                    (self.counter(state.searched), found)
+                };
+                if saved != Some(searched) {
+                    checkpoint::save(path, signature, Some((searched, &found))).unwrap_or_else(
+                        |e| eprintln!("Could not update {}: {}", path.display(), e),
+                    );
+                    saved = Some(searched);
+                }
+            }
+        }
+
+        if printed {
+            eprintln!();
+        }
+    }
+
+    // identifies a search in checkpoint files
+    fn signature(&self, algorithm: &str, count: usize) -> String {
+        format!(
+            "{} key={:?} pattern={} start={} step={} count={}",
+            algorithm,
+            String::from_utf8_lossy(&self.key),
+            self.pattern,
+            self.start,
+            self.step,
+            count
+        )
+    }
+
+    fn counter(&self, index: u64) -> u64 {
+        self.start + index * self.step
+    }
+
+    fn index(&self, counter: u64) -> u64 {
+        (counter - self.start) / self.step
+    }
+}
+
+// Checkpoint files hold one line per interrupted search: its signature, the
+// counter every counter below which has been searched, and the matches found
+// below it.
+mod checkpoint {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    pub fn load(path: &Path, signature: &str) -> Option<(u64, Vec<u64>)> {
+        let text = fs::read_to_string(path).ok()?;
+        let line = text
+            .lines()
+            .find(|line| line.split('\t').next() == Some(signature))?;
+
+        let mut fields = line.split('\t').skip(1);
+        let searched = fields.next()?.parse().ok()?;
+        let found = fields
+            .next()?
+            .split(',')
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse().ok())
+            .collect::<Option<_>>()?;
+        Some((searched, found))
+    }
+
+    // replaces the line of the search, or removes it when `entry` is None
+    pub fn save(path: &Path, signature: &str, entry: Option<(u64, &[u64])>) -> io::Result<()> {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        let mut lines: Vec<String> = text
+            .lines()
+            .filter(|line| line.split('\t').next() != Some(signature))
+            .map(str::to_string)
+            .collect();
+        if let Some((searched, found)) = entry {
+            let found: Vec<String> = found.iter().map(u64::to_string).collect();
+            lines.push(format!("{}\t{}\t{}", signature, searched, found.join(",")));
+        }
+
+        if lines.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        // written next to the file and renamed, so Ctrl-C can't leave half a file
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, lines.join("\n") + "\n")?;
+        fs::rename(&temporary, path)
+    }
+}
+
+// writes `n` in decimal to the end of `buffer`, without allocating
+fn decimal(mut n: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buffer[start..];
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let reference = |key: &str, pattern: &Pattern, start: u64, step: u64, count: usize| {
+        (0..)
+            .map(|i| start + i * step)
+            .filter(|n| pattern.matches(&md5::compute(format!("{}{}", key, n)).0))
+            .take(count)
+            .collect::<Vec<u64>>()
+    };
+
+    let five = Pattern::zeros(5);
+    assert_eq!(
+        Miner::new("abcdef", five.clone())
+            .threads(1)
+            .mine(Algorithm::Md5, 1),
+        vec![609043]
+    );
+    assert_eq!(
+        Miner::new("pqrstuv", five)
+            .threads(3)
+            .mine(Algorithm::Md5, 1),
+        vec![1048970]
+    );
+
+    for (pattern, start, step) in [("000", 1, 1), ("0b0000000000001", 100, 7), ("00a", 5000, 3)] {
+        let pattern: Pattern = pattern.parse().unwrap();
+        let expected = reference("abcdef", &pattern, start, step, 5);
+        for threads in [1, 2, 4] {
+            let miner = Miner::new("abcdef", pattern.clone())
+                .start(start)
+                .step(step)
+                .threads(threads);
+            assert_eq!(miner.mine(Algorithm::Md5, 5), expected);
+        }
+    }
+
+    // a batch holds hundreds of these, and every one of them must be checked
+    // even once enough matches were found, or later threads' earlier matches
+    // are lost
+    let zero: Pattern = "0".parse().unwrap();
+    let expected = reference("abcdef", &zero, 1, 1, 300);
+    for threads in [1, 4, 8] {
+        let miner = Miner::new("abcdef", zero.clone()).threads(threads);
+        assert_eq!(miner.mine(Algorithm::Md5, 300), expected);
+    }
+
+    assert_eq!(
+        Pattern::hex("0a").unwrap(),
+        Pattern {
+            bytes: vec![0x0a],
+            mask: vec![0xff]
+        }
+    );
+    assert_eq!(
+        Pattern::hex("00f").unwrap(),
+        Pattern {
+            bytes: vec![0x00, 0xf0],
+            mask: vec![0xff, 0xf0]
+        }
+    );
+    assert_eq!(
+        "0b101".parse::<Pattern>().unwrap(),
+        Pattern {
+            bytes: vec![0xa0],
+            mask: vec![0xe0]
+        }
+    );
+    assert_eq!(Pattern::zeros(3), Pattern::bits(&"0".repeat(12)).unwrap());
+    assert!(Pattern::zeros(3).matches(&[0x00, 0x0f, 0xff]));
+    assert!(!Pattern::zeros(3).matches(&[0x00, 0x1f, 0xff]));
+    assert!(!Pattern::zeros(3).matches(&[0x00]));
+    assert!(Pattern::zeros(0).matches(&[0xff]));
+    assert!("0x12".parse::<Pattern>().is_err());
+    assert!("0b012".parse::<Pattern>().is_err());
+    assert!("md4".parse::<Algorithm>().is_err());
+
+    // a checkpoint past the first match resumes there and finds the second one
+    let path = std::env::temp_dir().join(format!("miner-test-{}.txt", std::process::id()));
+    let miner = Miner::new("abcdef", Pattern::zeros(3)).checkpoint(&path);
+    let [first, second] = reference("abcdef", &Pattern::zeros(3), 1, 1, 2)[..] else {
+        panic!("Expected two matches");
+    };
+    let signature = miner.signature("md5", 1);
+    checkpoint::save(&path, "other search", Some((7, &[3, 5]))).unwrap();
+    checkpoint::save(&path, &signature, Some((first + 1, &[]))).unwrap();
+    assert_eq!(
+        checkpoint::load(&path, &signature),
+        Some((first + 1, vec![]))
+    );
+    assert_eq!(
+        checkpoint::load(&path, "other search"),
+        Some((7, vec![3, 5]))
+    );
+    assert_eq!(miner.mine(Algorithm::Md5, 1), vec![second]);
+
+    // matches below the saved counter are kept, and finished searches removed
+    checkpoint::save(&path, &signature, Some((second + 1, &[first]))).unwrap();
+    assert_eq!(miner.mine(Algorithm::Md5, 1), vec![first]);
+    assert_eq!(checkpoint::load(&path, &signature), None);
+    checkpoint::save(&path, "other search", None).unwrap();
+    assert!(!path.exists());
+
+    let mut buffer = [0; 20];
+    assert_eq!(decimal(0, &mut buffer), b"0");
+    assert_eq!(
+        decimal(u64::MAX, &mut buffer),
+        u64::MAX.to_string().as_bytes()
+    );
+
+    #[cfg(feature = "sha1")]
+    {
+        // sha1("abc1") = 9ee036...
+        let miner = Miner::new("abc", Pattern::hex("9ee").unwrap()).threads(2);
+        assert_eq!(miner.mine(Algorithm::Sha1, 1)[0], 1);
+    }
+    #[cfg(feature = "sha2")]
+    {
+        // sha256("abc1") = dbfcfd...
+        let miner = Miner::new("abc", Pattern::hex("dbf").unwrap()).threads(2);
+        assert_eq!(miner.mine(Algorithm::Sha256, 1)[0], 1);
+    }
+}
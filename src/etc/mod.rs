@@ -1,3 +1,12 @@
+pub mod alloc;
+pub mod bench;
+pub mod config;
+pub mod image;
+pub mod miner;
 pub mod solution;
+pub mod trace;
+pub mod validate;
+pub mod watch;
 
-pub use solution::Solution;
\ No newline at end of file
+pub use solution::Solution;
+pub use validate::Violation;
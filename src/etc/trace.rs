@@ -0,0 +1,235 @@
+use super::alloc::{self, AllocStats};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::write;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+// Lightweight span instrumentation. Spans are only recorded once `enable` has
+// been called, so leaving `span` calls in the solutions costs a single atomic
+// load per call when tracing is off. Keep them out of per-cell inner loops.
+// With the `alloc-stats` feature every recorded span also records its heap
+// usage, and the `part1` and `part2` spans are measured even when tracing is
+// off, without recording anything else.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+static RECORDS: Mutex<Vec<SpanRecord>> = Mutex::new(Vec::new());
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    static STACK: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Debug)]
+pub struct SpanRecord {
+    pub name: Cow<'static, str>,
+    pub parent: Option<usize>,
+    pub thread: u64,
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    pub alloc: Option<AllocStats>,
+}
+
+/// Guard returned by `span`, the span ends when it is dropped.
+pub struct Span {
+    id: Option<usize>,
+    part: Option<usize>,
+    scope: Option<alloc::Scope>,
+}
+
+pub fn enable() {
+    EPOCH.get_or_init(Instant::now);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Opens a span nested under the innermost open span of the current thread.
+pub fn span(name: impl Into<Cow<'static, str>>) -> Span {
+    let name = name.into();
+    let part = if alloc::ENABLED {
+        alloc::PARTS.iter().position(|part| *part == name)
+    } else {
+        None
+    };
+
+    if !is_enabled() {
+        return Span {
+            id: None,
+            part,
+            scope: part.map(|_| alloc::begin()),
+        };
+    }
+
+    let parent = STACK.with(|stack| stack.borrow().last().copied());
+    let record = SpanRecord {
+        name,
+        parent,
+        thread: THREAD.with(|t| *t),
+        start_ns: now_ns(),
+        duration_ns: 0,
+        alloc: None,
+    };
+
+    let id = {
+        let mut records = RECORDS.lock().unwrap();
+        records.push(record);
+        records.len() - 1
+    };
+    STACK.with(|stack| stack.borrow_mut().push(id));
+
+    Span {
+        id: Some(id),
+        part,
+        scope: alloc::ENABLED.then(alloc::begin),
+    }
+}
+
+/// Runs `f` inside a span named `name`.
+pub fn in_span<T>(name: impl Into<Cow<'static, str>>, f: impl FnOnce() -> T) -> T {
+    let _span = span(name);
+    f()
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let end = now_ns();
+        let stats = self.scope.take().map(alloc::end);
+        if let (Some(part), Some(stats)) = (self.part, stats) {
+            alloc::record_part(part, stats);
+        }
+
+        let Some(id) = self.id else {
+            return;
+        };
+        STACK.with(|stack| stack.borrow_mut().retain(|open| *open != id));
+        let record = &mut RECORDS.lock().unwrap()[id];
+        record.duration_ns = end - record.start_ns;
+        record.alloc = stats;
+    }
+}
+
+fn now_ns() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Removes and returns every span recorded so far.
+pub fn take() -> Vec<SpanRecord> {
+    std::mem::take(&mut *RECORDS.lock().unwrap())
+}
+
+/// Renders the spans as an indented tree. Sibling spans sharing a name are
+/// merged into one line with their call count, total time and, when heap
+/// usage was recorded, their largest peak and total allocation count.
+pub fn format_tree(records: &[SpanRecord], indent: usize) -> String {
+    let roots: Vec<usize> = (0..records.len())
+        .filter(|&id| records[id].parent.is_none())
+        .collect();
+
+    let mut out = String::new();
+    format_level(records, &roots, indent, &mut out);
+    out
+}
+
+fn format_level(records: &[SpanRecord], ids: &[usize], indent: usize, out: &mut String) {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for &id in ids {
+        let name = records[id].name.as_ref();
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, members)) => members.push(id),
+            None => groups.push((name, vec![id])),
+        }
+    }
+
+    for (name, members) in groups {
+        let total_ms = members
+            .iter()
+            .map(|&id| records[id].duration_ns)
+            .sum::<u64>() as f64
+            / 1_000_000.0;
+        let label = match members.len() {
+            1 => name.to_string(),
+            n => format!("{} ×{}", name, n),
+        };
+        let _ = write!(
+            out,
+            "{:indent$}{:<width$} {:>12.4} ms",
+            "",
+            label,
+            total_ms,
+            indent = indent,
+            width = 40usize.saturating_sub(indent),
+        );
+        let stats: Vec<AllocStats> = members.iter().filter_map(|&id| records[id].alloc).collect();
+        if !stats.is_empty() {
+            let merged = AllocStats {
+                peak_bytes: stats.iter().map(|s| s.peak_bytes).max().unwrap_or(0),
+                allocations: stats.iter().map(|s| s.allocations).sum(),
+            };
+            let _ = write!(out, "  ({})", merged);
+        }
+        out.push('\n');
+
+        let children: Vec<usize> = (0..records.len())
+            .filter(|&id| records[id].parent.is_some_and(|p| members.contains(&p)))
+            .collect();
+        format_level(records, &children, indent + 2, out);
+    }
+}
+
+/// Writes the spans in the Chrome trace event format, which can be opened
+/// with chrome://tracing or Perfetto.
+pub fn write_chrome_trace(path: &str, records: &[SpanRecord]) -> io::Result<()> {
+    let mut json = String::from("{\"traceEvents\":[\n");
+    for (i, record) in records.iter().enumerate() {
+        let _ = write!(
+            json,
+            "{}{{\"name\":{},\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+            if i == 0 { "" } else { ",\n" },
+            json_string(&record.name),
+            record.thread,
+            record.start_ns as f64 / 1000.0,
+            record.duration_ns as f64 / 1000.0,
+        );
+    }
+    json.push_str("\n]}\n");
+    write(path, json)
+}
+
+/// `s` as a JSON string literal, quotes included.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            // JSON is UTF-8, any other character can stay as it is
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn test() {
+    assert_eq!(json_string("part1"), "\"part1\"");
+    assert_eq!(
+        json_string("say \"hi\"\\n\t\u{1}\u{7f}"),
+        "\"say \\\"hi\\\"\\\\n\\t\\u0001\\u007f\""
+    );
+    assert_eq!(json_string("rätsel 'é' ✓"), "\"rätsel 'é' ✓\"");
+}
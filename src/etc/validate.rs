@@ -0,0 +1,205 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// A grammar violation in a puzzle input. Lines and columns are 1-based, line 0
+/// marks a problem with the file as a whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            _ => write!(
+                f,
+                "line {}, column {}: {}",
+                self.line, self.column, self.message
+            ),
+        }
+    }
+}
+
+/// The canonical form of an input file: CRLF line endings become LF, every
+/// line loses its trailing whitespace and leading/trailing blank lines are
+/// dropped, including the final newline.
+pub fn normalize(input: &str) -> String {
+    input
+        .replace("\r\n", "\n")
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Validates an input made of a single run of characters drawn from `allowed`,
+/// such as the parenthesis string of day 1. Line breaks are violations too,
+/// except for a single trailing newline at the end of the file.
+pub fn check_chars(input: &str, allowed: &[char]) -> Vec<Violation> {
+    let mut checker = CharChecker::new(allowed);
+    checker.feed(input.as_bytes());
+    checker.finish()
+}
+
+/// `check_chars` for inputs read in chunks, so they never have to be held in
+/// memory whole. Chunks may split a character, bytes that aren't UTF-8 are
+/// reported as U+FFFD.
+pub struct CharChecker<'a> {
+    allowed: &'a [char],
+    line: usize,
+    column: usize,
+    // a newline is only checked once something follows it
+    newline: Option<(usize, usize)>,
+    // the start of a character split across chunks
+    partial: Vec<u8>,
+    empty: bool,
+    violations: Vec<Violation>,
+}
+
+impl<'a> CharChecker<'a> {
+    pub fn new(allowed: &'a [char]) -> Self {
+        Self {
+            allowed,
+            line: 1,
+            column: 1,
+            newline: None,
+            partial: Vec::new(),
+            empty: true,
+            violations: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let joined;
+        let mut rest = if self.partial.is_empty() {
+            bytes
+        } else {
+            joined = [std::mem::take(&mut self.partial).as_slice(), bytes].concat();
+            joined.as_slice()
+        };
+
+        loop {
+            let error = match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    text.chars().for_each(|c| self.check(c));
+                    return;
+                }
+                Err(error) => error,
+            };
+            let (valid, invalid) = rest.split_at(error.valid_up_to());
+            for c in std::str::from_utf8(valid).unwrap().chars() {
+                self.check(c);
+            }
+            match error.error_len() {
+                Some(len) => {
+                    self.check(char::REPLACEMENT_CHARACTER);
+                    rest = &invalid[len..];
+                }
+                None => {
+                    self.partial = invalid.to_vec();
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<Violation> {
+        if !self.partial.is_empty() {
+            self.check(char::REPLACEMENT_CHARACTER);
+        }
+        if self.empty {
+            self.violations.push(Violation::new(1, 1, "input is empty"));
+        }
+        self.violations
+    }
+
+    fn check(&mut self, c: char) {
+        if let Some((line, column)) = self.newline.take() {
+            self.empty = false;
+            if !self.allowed.contains(&'\n') {
+                self.violations
+                    .push(Violation::new(line, column, "unexpected '\\n'"));
+            }
+            self.line += 1;
+            self.column = 1;
+        }
+
+        if c == '\n' {
+            self.newline = Some((self.line, self.column));
+            return;
+        }
+
+        self.empty = false;
+        if !self.allowed.contains(&c) {
+            self.violations.push(Violation::new(
+                self.line,
+                self.column,
+                format!("unexpected {:?}", c),
+            ));
+        }
+        self.column += 1;
+    }
+}
+
+/// Validates a line-oriented input. Whitespace problems (CRLF endings, stray
+/// spaces, blank lines) are reported here, every other line is handed to
+/// `check`, which returns the 1-based column and a description on failure.
+/// A single trailing newline at the end of the file is accepted.
+pub fn check_lines<F>(input: &str, check: F) -> Vec<Violation>
+where
+    F: Fn(&str) -> std::result::Result<(), (usize, String)>,
+{
+    check_raw_lines(input, |line| {
+        if line.trim().is_empty() {
+            Err((1, "empty line".to_string()))
+        } else if line.starts_with(char::is_whitespace) {
+            Err((1, "leading whitespace".to_string()))
+        } else if line.ends_with(char::is_whitespace) {
+            let column = line.trim_end().chars().count() + 1;
+            Err((column, "trailing whitespace".to_string()))
+        } else {
+            check(line)
+        }
+    })
+}
+
+/// `check_lines` for grammars with whitespace rules of their own: only CRLF
+/// endings are reported here, and every other line is handed to `check`
+/// untouched, blank or not.
+pub fn check_raw_lines<F>(input: &str, check: F) -> Vec<Violation>
+where
+    F: Fn(&str) -> std::result::Result<(), (usize, String)>,
+{
+    let mut violations = Vec::new();
+    let body = input.strip_suffix('\n').unwrap_or(input);
+
+    for (i, line) in body.split('\n').enumerate() {
+        let line_no = i + 1;
+
+        if let Some(stripped) = line.strip_suffix('\r') {
+            violations.push(Violation::new(
+                line_no,
+                stripped.chars().count() + 1,
+                "CRLF line ending",
+            ));
+        } else if let Err((column, message)) = check(line) {
+            violations.push(Violation::new(line_no, column, message));
+        }
+    }
+
+    violations
+}
@@ -0,0 +1,158 @@
+use super::config;
+use std::fs::{metadata, read_dir};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+// `watch <day>` polls the day's source file, its input and its test fixtures,
+// and on every change re-runs the day's tests and solver through cargo, so
+// edits are rebuilt. Answers are compared against the previous run.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Answers = (String, String);
+
+pub fn watch(day: u8, flags: &[String]) -> ! {
+    let mut stamps = modified_times(day);
+    let mut previous = None;
+
+    println!(
+        "Watching {} file(s) for day {:02}, press Ctrl-C to stop",
+        stamps.len(),
+        day
+    );
+    run(day, flags, &mut previous);
+
+    loop {
+        sleep(POLL_INTERVAL);
+
+        let current = modified_times(day);
+        if current != stamps {
+            stamps = current;
+            run(day, flags, &mut previous);
+        }
+    }
+}
+
+fn watched_paths(day: u8) -> Vec<PathBuf> {
+    let mut paths = vec![
+        PathBuf::from(format!("src/days/day{:02}.rs", day)),
+        PathBuf::from(config::input_path(day)),
+    ];
+
+    // fixtures are named `dayNN.txt`, `dayNN-extra.txt`, ...
+    let prefix = format!("day{:02}", day);
+    if let Ok(entries) = read_dir("input/test") {
+        let mut fixtures: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix))
+            })
+            .collect();
+        fixtures.sort();
+        paths.extend(fixtures);
+    }
+
+    paths
+}
+
+// a missing file counts as a state of its own, so creating or deleting a
+// watched file triggers a run as well
+fn modified_times(day: u8) -> Vec<(PathBuf, Option<SystemTime>)> {
+    watched_paths(day)
+        .into_iter()
+        .map(|path| {
+            let modified = metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+fn run(day: u8, flags: &[String], previous: &mut Option<Answers>) {
+    println!("\n=== Day {:02} · tests ===", day);
+    let tests = Command::new("cargo")
+        .args(["test", "--release", "--quiet"])
+        .arg(format!("days::day{:02}::", day))
+        .status();
+    match tests {
+        Ok(status) if status.success() => println!("  · Tests passed"),
+        Ok(_) => println!("  · Tests failed"),
+        Err(e) => println!("  · Could not run cargo: {}", e),
+    }
+
+    println!("\n=== Day {:02} · solution ===", day);
+    let output = Command::new("cargo")
+        .args(["run", "--release", "--quiet", "--"])
+        .arg(day.to_string())
+        .args(flags)
+        .arg("--format=json")
+        .stderr(Stdio::inherit())
+        .output();
+    let stdout = match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(e) => {
+            println!("  · Could not run cargo: {}", e);
+            return;
+        }
+    };
+
+    let report = stdout.lines().find(|line| line.contains("\"day\":"));
+    let answers =
+        report.and_then(|line| Some((json_field(line, "part1")?, json_field(line, "part2")?)));
+    let Some(answers) = answers else {
+        println!("  · No answers, solver output was:");
+        print!("{}", stdout);
+        return;
+    };
+
+    let elapsed = report.and_then(|line| json_field(line, "elapsed_ms"));
+    let old = previous.as_ref();
+    print_answer("Part 1", &answers.0, old.map(|a| &a.0));
+    print_answer("Part 2", &answers.1, old.map(|a| &a.1));
+    if let Some(elapsed) = elapsed {
+        println!("  · Elapsed: {} ms", elapsed);
+    }
+
+    *previous = Some(answers);
+}
+
+fn print_answer(label: &str, answer: &str, previous: Option<&String>) {
+    match previous {
+        None => println!("  · {}: {}", label, answer),
+        Some(old) if old == answer => println!("  · {}: {} (unchanged)", label, answer),
+        Some(old) => println!("  · {}: {} (was {})", label, answer, old),
+    }
+}
+
+// Reads a top-level scalar out of one of the runner's JSON report lines.
+// Strings are returned without their quotes and escapes are kept as is.
+fn json_field(line: &str, name: &str) -> Option<String> {
+    let start = line.find(&format!("\"{}\":", name))? + name.len() + 3;
+    let rest = &line[start..];
+
+    if let Some(string) = rest.strip_prefix('"') {
+        let mut escaped = false;
+        let end = string.find(|c| {
+            let quote = c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+            quote
+        })?;
+        return Some(string[..end].to_string());
+    }
+
+    let end = rest.find([',', '}']).unwrap_or(rest.len());
+    Some(rest[..end].to_string())
+}
+
+#[test]
+fn test() {
+    let line = r#"{"day":1,"part1":74,"part2":"a \"b\", c","elapsed_ms":0.1234}"#;
+    assert_eq!(json_field(line, "part1").as_deref(), Some("74"));
+    assert_eq!(json_field(line, "part2").as_deref(), Some(r#"a \"b\", c"#));
+    assert_eq!(json_field(line, "elapsed_ms").as_deref(), Some("0.1234"));
+    assert_eq!(json_field(line, "memory"), None);
+}
@@ -1,45 +1,344 @@
 mod days;
 mod etc;
 
-use etc::solution::Solution;
+use etc::config::{self, Config, Format};
+use etc::{alloc, trace, validate, watch, Solution, Violation};
 use days::{day01, day02, day03, day04, day05,
            day06, day07, day08, day09, day10,
            day11, day12, day13, day14, day15,
            day16, day17, day18, day19, day20,
            day21, day22, day23, day24, day25};
 use std::env;
-use std::time::Instant;
+use std::fs::{read_to_string, write, File};
+use std::io::{self, Read};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub type SolutionPair = (Solution, Solution);
+type ReaderValidator = fn(&mut dyn Read) -> io::Result<Vec<Violation>>;
+
+// flags that never consume the following argument as their value
+const BARE_FLAGS: [&str; 2] = ["trace", "fix"];
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (positional, flags) = split_args(&args);
+    if positional.is_empty() {
         panic!("Please provide the day(s) to run as a command-line argument.");
     }
 
-    let days: Vec<u8> = args[1..].iter()
-        .map(|x| x.parse().unwrap_or_else(|v| panic!("Not a valid day: {}", v)))
-        .collect();
+    let mut config = Config::load().unwrap_or_else(|e| panic!("{}", e));
+    for (name, value) in flags.iter().filter(|(name, _)| !BARE_FLAGS.contains(&name.as_str())) {
+        let value = value.as_deref().unwrap_or("true");
+        config.apply_flag(name, value).unwrap_or_else(|e| panic!("{}", e));
+    }
+    config::init(config);
+
+    let flag = |name: &str| flags.iter().find(|(flag, _)| flag == name).map(|(_, value)| value.clone());
+
+    if positional[0] == "check" {
+        return check(&parse_days(&positional[1..]), flag("fix").is_some());
+    }
+
+    if positional[0] == "bench" {
+        return bench(&parse_days(&positional[1..]));
+    }
+
+    if positional[0] == "watch" {
+        let [day] = parse_days(&positional[1..])[..] else {
+            panic!("Please provide exactly one day to watch.");
+        };
+        // the watched runs get the same flags, `watch` has none of its own
+        let forwarded: Vec<String> = flags.iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("--{}={}", name, value),
+                None => format!("--{}", name),
+            })
+            .collect();
+        watch::watch(day, &forwarded);
+    }
+
+    run(&parse_days(&positional), flag("trace"));
+}
+
+// Splits the arguments into positional ones and `--name value` / `--name=value`
+// flags. A flag followed by another flag, or by nothing, has no value.
+fn split_args(args: &[String]) -> (Vec<String>, Vec<(String, Option<String>)>) {
+    let mut positional = Vec::new();
+    let mut flags = Vec::new();
+
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            positional.push(arg.clone());
+            continue;
+        };
+
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None if BARE_FLAGS.contains(&flag) => (flag.to_string(), None),
+            None => (flag.to_string(), args.next_if(|x| !x.starts_with("--")).cloned()),
+        };
+        flags.push((name, value));
+    }
+
+    (positional, flags)
+}
+
+#[test]
+fn test() {
+    let args = |line: &str| split_args(&line.split(' ').map(str::to_string).collect::<Vec<_>>());
+
+    // only the exact name makes a flag bare
+    let (positional, flags) = args("6 --trace 7 --traceXYZ 8 --fix --trace=out.json");
+    assert_eq!(positional, vec!["6", "7"]);
+    assert_eq!(flags, vec![
+        ("trace".to_string(), None),
+        ("traceXYZ".to_string(), Some("8".to_string())),
+        ("fix".to_string(), None),
+        ("trace".to_string(), Some("out.json".to_string())),
+    ]);
+}
+
+// `all` stands for the enabled days from the configuration
+fn parse_days(args: &[String]) -> Vec<u8> {
+    args.iter()
+        .flat_map(|x| match x.as_str() {
+            "all" => config::get().days.clone(),
+            _ => vec![x.parse().unwrap_or_else(|v| panic!("Not a valid day: {}", v))],
+        })
+        .collect()
+}
+
+// `--trace` prints a timing tree per day, `--trace=file.json` writes a Chrome
+// trace of the whole run instead
+fn run(days: &[u8], trace_flag: Option<Option<String>>) {
+    let config = config::get();
+    let trace_file = trace_flag.clone().flatten();
+    let print_trace = trace_flag.is_some() && trace_file.is_none() && config.format == Format::Text;
+
+    if trace_flag.is_some() {
+        trace::enable();
+    }
+
+    if config.format == Format::Text {
+        println!("Advent of Code {}", config.year);
+    }
 
     let mut runtime = 0.0;
+    let mut trace_records = Vec::new();
+
+    for &day in days {
+        if let Err((path, violations)) = check_input(day) {
+            match config.format {
+                Format::Text => {
+                    println!("\n=== Day {:02} ===", day);
+                    print_violations(&path, &violations);
+                    println!("  · Skipped, run `check {}` for details", day);
+                }
+                Format::Json => {
+                    let violations: Vec<String> = violations.iter().map(|v| trace::json_string(&v.to_string())).collect();
+                    println!("{{\"day\":{},\"input\":{},\"violations\":[{}]}}", day, trace::json_string(&path), violations.join(","));
+                }
+            }
+            continue;
+        }
 
-    for day in days {
         let func = get_day_solver(day);
 
+        let scope = alloc::ENABLED.then(alloc::begin);
         let time = Instant::now();
-        let (p1, p2) = func();
+        let result = run_solver(day, func, config.timeout);
         let elapsed_ms = time.elapsed().as_nanos() as f64 / 1_000_000.0;
-        
-        println!("\n=== Day {:02} ===", day);
-        println!("  · Part 1: {}", p1);
-        println!("  · Part 2: {}", p2);
-        println!("  · Elapsed: {:.4} ms", elapsed_ms);
+        let memory = memory_stats(day, scope.map(alloc::end));
+        let records = trace::take();
+
+        match config.format {
+            Format::Text => {
+                println!("\n=== Day {:02} ===", day);
+                match &result {
+                    Some((p1, p2)) => {
+                        println!("  · Part 1: {}", p1);
+                        println!("  · Part 2: {}", p2);
+                    }
+                    None => println!("  · Timed out"),
+                }
+                println!("  · Elapsed: {:.4} ms", elapsed_ms);
+
+                print_memory(&memory);
+                if print_trace {
+                    println!("  · Trace:");
+                    print!("{}", trace::format_tree(&records, 6));
+                }
+            }
+            Format::Json => println!("{}", json_report(day, &result, elapsed_ms, &memory)),
+        }
 
+        trace_records.extend(records);
         runtime += elapsed_ms;
     }
 
-    println!("Total runtime: {:.4} ms", runtime);
+    if config.format == Format::Text {
+        println!("Total runtime: {:.4} ms", runtime);
+    }
+
+    if let Some(path) = trace_file {
+        trace::write_chrome_trace(&path, &trace_records)
+            .unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+        eprintln!("Trace written to {}", path);
+    }
+}
+
+// Runs the solver inside a span named after the day. With a timeout the solver
+// runs on its own thread and is abandoned (left running until the process
+// exits) once the timeout expires, in which case `None` is returned.
+fn run_solver(day: u8, func: fn() -> SolutionPair, timeout: Option<Duration>) -> Option<SolutionPair> {
+    let solve = move || {
+        let _span = trace::span(format!("day{:02}", day));
+        func()
+    };
+
+    let Some(timeout) = timeout else {
+        return Some(solve());
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(solve()));
+    match receiver.recv_timeout(timeout) {
+        Ok(solution) => Some(solution),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => panic!("Day {} panicked", day),
+    }
+}
+
+// the heap usage of a day and of its parts, if measured
+fn memory_stats(day: u8, total: Option<alloc::AllocStats>) -> Vec<(String, alloc::AllocStats)> {
+    let Some(total) = total else {
+        return Vec::new();
+    };
+
+    let parts = alloc::PARTS.iter().zip(alloc::take_parts())
+        .filter_map(|(name, stats)| Some((name.to_string(), stats?)));
+    [(format!("day{:02}", day), total)].into_iter()
+        .chain(parts)
+        .collect()
+}
+
+fn print_memory(memory: &[(String, alloc::AllocStats)]) {
+    for (i, (name, stats)) in memory.iter().enumerate() {
+        match i {
+            0 => println!("  · Memory: {}", stats),
+            _ => println!("      {}: {}", name, stats),
+        }
+    }
+}
+
+fn json_report(day: u8, result: &Option<SolutionPair>, elapsed_ms: f64, memory: &[(String, alloc::AllocStats)]) -> String {
+    let mut fields = vec![format!("\"day\":{}", day)];
+    match result {
+        Some((p1, p2)) => {
+            fields.push(format!("\"part1\":{}", json_solution(p1)));
+            fields.push(format!("\"part2\":{}", json_solution(p2)));
+        }
+        None => fields.push("\"timed_out\":true".to_string()),
+    }
+    fields.push(format!("\"elapsed_ms\":{:.4}", elapsed_ms));
+
+    let memory: Vec<String> = memory.iter()
+        .map(|(name, stats)| format!("{}:{{\"peak_bytes\":{},\"allocations\":{}}}", trace::json_string(name), stats.peak_bytes, stats.allocations))
+        .collect();
+    if !memory.is_empty() {
+        fields.push(format!("\"memory\":{{{}}}", memory.join(",")));
+    }
+
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_solution(solution: &Solution) -> String {
+    match solution {
+        Solution::Str(s) => trace::json_string(s),
+        other => other.to_string(),
+    }
+}
+
+// `check <days...> [--fix]` validates each day's input against its grammar
+// and, when normalizing whitespace would make it valid, offers to rewrite it
+fn check(days: &[u8], fix: bool) {
+    for &day in days {
+        println!("\n=== Day {:02} ===", day);
+
+        let Some(validator) = get_day_validator(day) else {
+            println!("  · No input grammar for this day");
+            continue;
+        };
+
+        let path = config::input_path(day);
+        let Ok(input) = read_to_string(&path) else {
+            println!("  · Could not read {}", path);
+            continue;
+        };
+        let violations = validator(&input);
+        if violations.is_empty() {
+            println!("  · {}: OK", path);
+            continue;
+        }
+
+        print_violations(&path, &violations);
+
+        let normalized = validate::normalize(&input);
+        if !validator(&normalized).is_empty() {
+            println!("  · Normalizing (trim, CRLF → LF) does not fix this input");
+        } else if fix {
+            write(&path, normalized).unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+            println!("  · Rewrote {} in normalized form", path);
+        } else {
+            println!("  · The normalized input (trim, CRLF → LF) is valid, run `check {} --fix` to rewrite it", day);
+        }
+    }
+}
+
+// `bench <days...>` runs the benchmarks of the given days
+fn bench(days: &[u8]) {
+    for &day in days {
+        match get_day_bench(day) {
+            Some(bench) => bench(),
+            None => println!("\n=== Day {:02} ===\n  · No benchmark for this day", day),
+        }
+    }
+}
+
+// runs before every solver so bad or missing inputs are reported with line
+// numbers instead of panicking somewhere inside the solution
+fn check_input(day: u8) -> Result<(), (String, Vec<Violation>)> {
+    let Some(validator) = get_day_validator(day) else {
+        return Ok(());
+    };
+
+    let path = config::input_path(day);
+    let violations = match get_day_reader_validator(day) {
+        Some(validate) => File::open(&path).and_then(|mut file| validate(&mut file)),
+        None => read_to_string(&path).map(|input| validator(&input)),
+    };
+    let violations = violations.unwrap_or_else(|e| vec![Violation::new(0, 0, format!("could not read file: {}", e))]);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err((path, violations))
+    }
+}
+
+fn print_violations(path: &str, violations: &[Violation]) {
+    const SHOWN: usize = 10;
+
+    println!("  · {}: {} violation(s)", path, violations.len());
+    for violation in violations.iter().take(SHOWN) {
+        println!("      {}", violation);
+    }
+    if violations.len() > SHOWN {
+        println!("      ... and {} more", violations.len() - SHOWN);
+    }
 }
 
 fn get_day_solver(day: u8) -> fn() -> SolutionPair {
@@ -71,4 +370,33 @@ fn get_day_solver(day: u8) -> fn() -> SolutionPair {
         25 => day25::solve,
          _ => unimplemented!(),
     }
-}
\ No newline at end of file
+}
+
+fn get_day_validator(day: u8) -> Option<fn(&str) -> Vec<Violation>> {
+    match day {
+        1 => Some(day01::validate),
+        2 => Some(day02::validate),
+        3 => Some(day03::validate),
+        5 => Some(day05::validate),
+        6 => Some(day06::validate),
+        7 => Some(day07::validate),
+        _ => None,
+    }
+}
+
+// validators that read the input in chunks, for days with large inputs
+fn get_day_reader_validator(day: u8) -> Option<ReaderValidator> {
+    match day {
+        1 => Some(day01::validate_reader),
+        _ => None,
+    }
+}
+
+fn get_day_bench(day: u8) -> Option<fn()> {
+    match day {
+        1 => Some(day01::bench),
+        5 => Some(day05::bench),
+        6 => Some(day06::bench),
+        _ => None,
+    }
+}